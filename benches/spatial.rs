@@ -0,0 +1,98 @@
+//! `cargo bench --bench spatial` times the collision grid against checking every projectile
+//! against every enemy, the way the game did before it had a grid.
+//!
+//! Projectiles are swept along the path they take in a tick, like `resolve_player_projectiles` does,
+//! and the run fails if the grid ever finds different hits than the all-pairs loop.
+
+use std::time::{Duration, Instant};
+
+use bevy::prelude::*;
+use game_jam_entry::grid::{sweep_circle, SpatialGrid};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+/// These match the game's chasers and basic projectiles.
+const ENEMY_RADIUS: f32 = 40.0;
+const PROJECTILE_RADIUS: f32 = 15.0;
+/// How far a projectile moves in a tick at the default speed and 64 ticks per second.
+const PROJECTILE_STEP: f32 = 800.0 / 64.0;
+
+fn main() {
+    let mut failed = false;
+    for (enemies, projectiles) in [(100, 500), (300, 2000), (1000, 5000)] {
+        failed |= !run_benchmark(enemies, projectiles, 200, 0);
+    }
+    if failed {
+        std::process::exit(1);
+    }
+}
+
+/// Scatters enemies and projectiles around a crowded late-game sized area,
+/// then finds every projectile-enemy hit with both the all-pairs loop and the grid.
+/// Returns whether they found the same hits.
+fn run_benchmark(enemy_count: usize, projectile_count: usize, ticks: usize, seed: u64) -> bool {
+    const ARENA_RADIUS: f32 = 1500.0;
+
+    let mut rng = StdRng::seed_from_u64(seed);
+    let mut random_point = || Vec2::new(
+        rng.gen_range(-ARENA_RADIUS..ARENA_RADIUS),
+        rng.gen_range(-ARENA_RADIUS..ARENA_RADIUS),
+    );
+    let enemies = (0..enemy_count).map(|_| random_point()).collect::<Vec<_>>();
+    let projectiles = (0..projectile_count)
+        .map(|_| (random_point(), random_point().normalize_or_zero() * PROJECTILE_STEP))
+        .collect::<Vec<_>>();
+
+    let mut all_pairs_time = Duration::ZERO;
+    let mut grid_time = Duration::ZERO;
+    let mut agreed = true;
+    let mut hit_count = 0;
+    let mut grid = SpatialGrid::default();
+
+    for tick in 0..ticks {
+        let mut all_pairs_hits = Vec::new();
+        let mut grid_hits = Vec::new();
+
+        let start = Instant::now();
+        for (projectile_index, (position, step)) in projectiles.iter().enumerate() {
+            let (from, to) = travel(*position, *step, tick);
+            for (enemy_index, enemy) in enemies.iter().enumerate() {
+                if sweep_circle(from, to, *enemy, ENEMY_RADIUS + PROJECTILE_RADIUS).is_some() {
+                    all_pairs_hits.push((projectile_index, enemy_index));
+                }
+            }
+        }
+        all_pairs_time += start.elapsed();
+
+        let start = Instant::now();
+        grid.clear();
+        for (index, enemy) in enemies.iter().enumerate() {
+            grid.insert(Entity::from_raw(index as u32), *enemy, ENEMY_RADIUS);
+        }
+        for (projectile_index, (position, step)) in projectiles.iter().enumerate() {
+            let (from, to) = travel(*position, *step, tick);
+            for candidate in grid.candidates_along(from, to, PROJECTILE_RADIUS) {
+                if sweep_circle(from, to, candidate.position, candidate.radius + PROJECTILE_RADIUS).is_some() {
+                    grid_hits.push((projectile_index, candidate.entity.index() as usize));
+                }
+            }
+        }
+        grid_time += start.elapsed();
+
+        hit_count += all_pairs_hits.len();
+        agreed &= all_pairs_hits == grid_hits;
+    }
+
+    println!("{enemy_count} enemies, {projectile_count} projectiles, {ticks} ticks, {hit_count} hits");
+    println!("all pairs: {:>10.3?} per tick", all_pairs_time / ticks.max(1) as u32);
+    println!("grid:      {:>10.3?} per tick", grid_time / ticks.max(1) as u32);
+    if !agreed {
+        eprintln!("the grid missed or invented hits!");
+    }
+    agreed
+}
+
+/// Where a projectile starts and ends its move on a given tick.
+fn travel(position: Vec2, step: Vec2, tick: usize) -> (Vec2, Vec2) {
+    let from = position + step * tick as f32;
+    (from, from + step)
+}
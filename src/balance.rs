@@ -0,0 +1,1831 @@
+//! All of the numbers that decide how the game plays, in one place.
+//!
+//! The defaults are compiled in, and a RON file can override any of them.
+//! The game reads `balance.ron` from the current directory (or the file given with
+//! `--balance <file>`) at startup, and reads it again whenever it changes,
+//! so numbers can be tuned in the middle of a run without restarting.
+//! Anything left out of the file keeps its default value.
+
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{Boomerang, ChainLightning, EnemyKind, PlayerStats, PlayerWeapon, Ricochet, SeeksEnemies, SplitOnHit};
+
+pub const DEFAULT_BALANCE_PATH: &str = "balance.ron";
+
+/// Loads and validates a balance file.
+/// A missing file at the default path isn't an error, the compiled-in defaults are used instead.
+pub fn load_balance(path: &Path, required: bool) -> Result<Balance, String> {
+    let contents = match std::fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound && !required => {
+            return Ok(Balance::default());
+        },
+        Err(err) => return Err(format!("couldn't read balance file {}: {err}", path.display())),
+    };
+
+    let balance: Balance = ron::from_str(&contents)
+        .map_err(|err| format!("couldn't parse balance file {}: {err}", path.display()))?;
+
+    balance.validate().map_err(|problems| format!(
+        "balance file {} has some problems:\n  {}",
+        path.display(),
+        problems.join("\n  "),
+    ))?;
+
+    Ok(balance)
+}
+
+/// Takes `--balance <file>` out of the command line arguments and loads that file,
+/// or `balance.ron` if it wasn't given.
+/// Returns the balance along with the path to watch for changes.
+pub fn balance_from_args(args: &mut Vec<String>) -> Result<(Balance, PathBuf), String> {
+    let (path, required) = match args.iter().position(|arg| arg == "--balance") {
+        Some(index) => {
+            let path = args.get(index + 1).ok_or("`--balance` needs a value")?;
+            let path = PathBuf::from(path);
+            args.drain(index..=index + 1);
+            (path, true)
+        },
+        None => (PathBuf::from(DEFAULT_BALANCE_PATH), false),
+    };
+
+    let balance = load_balance(&path, required)?;
+    Ok((balance, path))
+}
+
+/// Reloads the balance file when it changes on disk.
+/// A file that doesn't parse or validate is reported and ignored,
+/// the game keeps going with the last good balance.
+pub fn hot_reload_balance(
+    time: Res<Time<Real>>,
+    mut source: ResMut<BalanceSource>,
+    mut balance: ResMut<Balance>,
+) {
+    const CHECK_INTERVAL: f32 = 0.5;
+
+    source.time_until_check -= time.delta_seconds();
+    if source.time_until_check > 0.0 {
+        return;
+    }
+    source.time_until_check = CHECK_INTERVAL;
+
+    let modified = std::fs::metadata(&source.path).and_then(|metadata| metadata.modified()).ok();
+    if modified.is_none() || modified == source.last_modified {
+        return;
+    }
+    source.last_modified = modified;
+
+    match load_balance(&source.path, true) {
+        Ok(new_balance) => {
+            info!("Reloaded balance from {}", source.path.display());
+            *balance = new_balance;
+        },
+        Err(err) => warn!("Keeping the previous balance: {err}"),
+    }
+}
+
+/// Where the balance was loaded from, for hot reloading.
+#[derive(Resource)]
+#[derive(Debug, Clone, PartialEq)]
+pub struct BalanceSource {
+    pub path: PathBuf,
+    pub last_modified: Option<SystemTime>,
+    time_until_check: f32,
+}
+
+#[derive(Resource, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct Balance {
+    pub player: PlayerBalance,
+    pub waves: WaveBalance,
+    pub enemies: EnemyBalance,
+    pub bosses: BossBalance,
+    pub upgrades: UpgradeBalance,
+    pub draft: DraftBalance,
+    pub weapons: WeaponBalance,
+    pub power_ups: PowerUpBalance,
+    pub score: ScoreBalance,
+}
+
+#[derive(Serialize, Deserialize)]
+#[derive(Debug, Copy, Clone, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct PlayerBalance {
+    pub health: usize,
+    pub stats: PlayerStats,
+}
+
+/// What spawns in each wave, and how strong enemies are as a function of the wave number.
+#[derive(Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct WaveBalance {
+    /// Which enemies spawn in which waves, and how. Each wave uses the last entry that covers it.
+    /// The default is one endless entry for every wave, with a boss entry on top every 10th wave.
+    pub script: Vec<ScriptedWave>,
+    /// Where each enemy is about to spawn is marked this many seconds before it shows up.
+    pub spawn_telegraph: f32,
+    /// Seconds between clearing a wave and the next one starting. 0 starts the next one straight away.
+    pub intermission: f32,
+    /// Calling the next wave early scores this much for every second of the intermission skipped.
+    pub early_call_score_per_second: f32,
+
+    /// Scattered enemies spawn between `spawn_distance_min` and
+    /// `spawn_distance_max_base + spawn_distance_max_per_wave * wave` away from the player.
+    pub spawn_distance_min: f32,
+    pub spawn_distance_max_base: f32,
+    pub spawn_distance_max_per_wave: f32,
+
+    pub enemy_health_base: usize,
+    pub enemy_health_per_wave: usize,
+
+    pub melee_damage_base: usize,
+    pub melee_damage_per_wave: usize,
+    /// The melee cooldown is multiplied by this for every wave after the first.
+    pub melee_cooldown_base: f32,
+    pub melee_cooldown_scaling: f32,
+
+    /// Movement and projectile speed are multiplied by `speed_scaling` every wave.
+    pub movement_speed_base: f32,
+    pub ranged_attack_speed_base: f32,
+    pub speed_scaling: f32,
+
+    pub ranged_attack_cooldown: f32,
+    /// Ranged damage is `(wave - ranged_damage_wave_offset) * ranged_damage_scaling ^ (wave - 10)`.
+    pub ranged_damage_wave_offset: isize,
+    pub ranged_damage_scaling: f32,
+
+    /// Checked in order, each with its own roll.
+    /// The first rule that applies to the wave and succeeds means the enemy isn't a shooter,
+    /// and its kind is picked from `enemies.composition` instead.
+    pub melee_only_rules: Vec<MeleeOnlyRule>,
+    /// There are no shooters from this wave on, whatever the rules rolled.
+    pub melee_only_from_wave: isize,
+}
+
+/// The enemies for one wave, or a range of waves.
+#[derive(Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct ScriptedWave {
+    pub from_wave: isize,
+    /// The last wave this covers. Without one, it goes on forever.
+    pub to_wave: Option<isize>,
+    /// Only covers every `every`th wave, counting from `from_wave`.
+    pub every: isize,
+    pub groups: Vec<SpawnGroup>,
+}
+
+/// A bunch of enemies that spawn together in the same pattern.
+#[derive(Serialize, Deserialize)]
+#[derive(Debug, Copy, Clone, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct SpawnGroup {
+    pub kind: GroupKind,
+    /// The group has `count + count_per_wave * (wave - from_wave)` enemies.
+    pub count: usize,
+    pub count_per_wave: usize,
+    pub pattern: SpawnPattern,
+    pub scaling: StatScaling,
+    /// Seconds into the wave before the group starts spawning.
+    pub delay: f32,
+}
+
+#[derive(Serialize, Deserialize)]
+#[derive(Debug, Copy, Clone, PartialEq, Default)]
+pub enum GroupKind {
+    /// Each enemy is a shooter if it rolls one with `waves.melee_only_rules`,
+    /// otherwise its kind is picked from `enemies.composition`.
+    #[default]
+    Mixed,
+    /// Every enemy is this kind.
+    Only(EnemyKind),
+}
+
+#[derive(Serialize, Deserialize)]
+#[derive(Debug, Copy, Clone, PartialEq, Default)]
+pub enum SpawnPattern {
+    /// Each enemy goes somewhere random within the wave's spawn distances.
+    #[default]
+    Scatter,
+    /// Evenly spaced in a circle `distance` away from the player.
+    Ring { distance: f32 },
+    /// Bunched up within `spread` of a random spot `distance` away from the player.
+    Cluster { distance: f32, spread: f32 },
+    /// Evenly spaced along a line `length` long, `distance` away on a random side of the player.
+    Line { distance: f32, length: f32 },
+    /// Scattered, but one at a time, `interval` seconds apart.
+    Trickle { interval: f32 },
+}
+
+/// Multiplies the stats a group's enemies get from their kind and wave.
+#[derive(Serialize, Deserialize)]
+#[derive(Debug, Copy, Clone, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct StatScaling {
+    pub health: f32,
+    pub damage: f32,
+    pub speed: f32,
+}
+
+#[derive(Serialize, Deserialize)]
+#[derive(Debug, Copy, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct MeleeOnlyRule {
+    /// The rule applies to waves before this one.
+    pub before_wave: isize,
+    pub chance: f32,
+}
+
+/// What sets each kind of enemy apart from the others.
+/// The multipliers apply on top of what `waves` gives every enemy in a wave.
+#[derive(Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct EnemyBalance {
+    pub chaser: EnemyKindStats,
+    pub shooter: EnemyKindStats,
+    pub dasher: EnemyKindStats,
+    pub brute: EnemyKindStats,
+    pub splitter: EnemyKindStats,
+    pub healer: EnemyKindStats,
+    pub boss: EnemyKindStats,
+
+    /// Shooters back away from the player when they're closer than 80% of this,
+    /// and close in when they're further away.
+    pub shooter_distance: f32,
+
+    /// Dashers within `dash_range` of the player dash straight at them
+    /// for `dash_duration` seconds, then wait `dash_cooldown` seconds before dashing again.
+    pub dash_range: f32,
+    pub dash_speed_multiplier: f32,
+    pub dash_duration: f32,
+    pub dash_cooldown: f32,
+
+    /// Splitters break into `split_count` chasers when they die,
+    /// each with `split_health_fraction` of a normal chaser's health.
+    pub split_count: usize,
+    pub split_health_fraction: f32,
+
+    /// Every `heal_cooldown` seconds, healers heal every other enemy within `heal_radius`
+    /// by `heal_fraction` of that enemy's max health.
+    pub heal_radius: f32,
+    pub heal_fraction: f32,
+    pub heal_cooldown: f32,
+
+    /// The mix of enemies that aren't shooters. Each wave uses the last entry it has reached.
+    /// Shooters aren't in here, they're still decided by `waves.melee_only_rules`.
+    pub composition: Vec<WaveComposition>,
+
+    /// Which kinds shoot, and how. Each kind uses the last entry for it that the wave has reached,
+    /// and kinds without one don't shoot at all.
+    pub shots: Vec<ShotRule>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[derive(Debug, Copy, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct EnemyKindStats {
+    pub radius: f32,
+    pub health_multiplier: f32,
+    pub melee_damage_multiplier: f32,
+    pub speed_multiplier: f32,
+    /// Enemies steer away from each other when they're closer than this,
+    /// with `separation_strength` deciding how much that matters compared to where they're headed.
+    pub separation_radius: f32,
+    pub separation_strength: f32,
+    /// Points for killing one.
+    pub score: u64,
+}
+
+/// When boss waves happen, and what bosses do in them.
+#[derive(Serialize, Deserialize)]
+#[derive(Debug, Copy, Clone, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct BossBalance {
+    /// Bosses move on to their second phase below this fraction of their health,
+    /// and to their third below `phase_three_below`.
+    pub phase_two_below: f32,
+    pub phase_three_below: f32,
+
+    /// All through the fight, a boss fires `ring_bullet_count` bullets in every direction
+    /// every `ring_cooldown` seconds, each doing `ring_damage_multiplier` times the wave's melee damage.
+    pub ring_bullet_count: usize,
+    pub ring_cooldown: f32,
+    pub ring_bullet_speed: f32,
+    pub ring_damage_multiplier: f32,
+
+    /// From the second phase on, it summons `summon_count` chasers every `summon_cooldown` seconds.
+    pub summon_count: usize,
+    pub summon_cooldown: f32,
+
+    /// In the third phase, it charges at the player every `charge_cooldown` seconds.
+    pub charge_cooldown: f32,
+    pub charge_duration: f32,
+    pub charge_speed_multiplier: f32,
+
+    /// Unused upgrades for killing a boss, on top of the one every wave gives.
+    pub reward_upgrades: usize,
+}
+
+/// How likely each kind is, relative to the others. Kinds left out never show up.
+#[derive(Serialize, Deserialize)]
+#[derive(Debug, Copy, Clone, PartialEq, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct WaveComposition {
+    pub from_wave: isize,
+    pub chaser: f32,
+    pub dasher: f32,
+    pub brute: f32,
+    pub splitter: f32,
+    pub healer: f32,
+}
+
+#[derive(Serialize, Deserialize)]
+#[derive(Debug, Copy, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct ShotRule {
+    pub kind: EnemyKind,
+    pub from_wave: isize,
+    pub pattern: ShotPattern,
+}
+
+/// What an enemy fires every time its ranged attack is ready.
+/// Every shot does the wave's ranged damage and moves at its ranged attack speed, unless it says otherwise.
+#[derive(Serialize, Deserialize)]
+#[derive(Debug, Copy, Clone, PartialEq, Default)]
+pub enum ShotPattern {
+    /// One shot at where the player is.
+    #[default]
+    Aimed,
+    /// One shot at where the player will be, if they keep moving the same way.
+    Leading,
+    /// `count` shots spread evenly over `spread` radians, centred on the player.
+    Fan { count: usize, spread: f32 },
+    /// `count` shots evenly spaced in every direction, starting with one at the player.
+    Radial { count: usize },
+    /// One slow shot that turns towards the player by up to `turn_rate` radians a second,
+    /// until it fizzles out after `lifetime` seconds.
+    Homing { speed_multiplier: f32, turn_rate: f32, lifetime: f32 },
+    /// A mine left where the enemy is, which blows up after `fuse` seconds
+    /// and hurts the player if they're within `radius` of it.
+    Mine { fuse: f32, radius: f32 },
+}
+
+#[derive(Serialize, Deserialize)]
+#[derive(Debug, Copy, Clone, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct UpgradeBalance {
+    pub attack_ranged_damage: usize,
+    pub attack_ranged_cooldown_multiplier: f32,
+    pub attack_close_damage: usize,
+
+    pub health_max_hp: usize,
+    /// Heals `max_hp / health_heal_divisor` straight away.
+    pub health_heal_divisor: usize,
+    pub health_end_of_round_heal: usize,
+
+    pub speed_movement_multiplier: f32,
+    pub speed_ranged_attack_speed_multiplier: f32,
+}
+
+#[derive(Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct WeaponBalance {
+    /// Every weapon there is, in the order Q and E cycle through them.
+    pub roster: Vec<WeaponDefinition>,
+
+    /// The total burst damage (damage times pierce) up to which
+    /// a burst fires 5 small pellets, then up to `medium_burst_max_total` 7 pellets,
+    /// and 9 pellets after that.
+    pub small_burst_max_total: usize,
+    pub medium_burst_max_total: usize,
+    /// Radians between pellets, for the small, medium and large bursts.
+    pub burst_spreads: [f32; 3],
+}
+
+/// One weapon in the roster. Anything left out is the same as a plain single shot.
+#[derive(Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct WeaponDefinition {
+    pub name: String,
+    /// The wave it can first be picked in. Anything from wave 1 or before is there from the start.
+    pub unlock_wave: isize,
+    pub firing: WeaponFiring,
+    /// Each hit does the player's ranged damage times `damage_multiplier`, plus `damage_bonus`,
+    /// which can be negative.
+    pub damage_multiplier: f32,
+    pub damage_bonus: isize,
+    pub cooldown_multiplier: f32,
+    pub speed_multiplier: f32,
+    /// The player's pierce is multiplied by this, then `pierce_bonus` is added.
+    pub pierce_multiplier: f32,
+    pub pierce_bonus: usize,
+    pub radius: f32,
+    /// Red, green and blue, from 0 to 1.
+    pub color: (f32, f32, f32),
+    /// Projectiles disappear after this many seconds, if it's set.
+    pub lifetime: Option<f32>,
+    /// Projectiles leave a splash behind everywhere they hit.
+    pub splash: Option<WeaponSplash>,
+    /// Only weapons that fire projectiles use these.
+    pub modifiers: ProjectileModifiers,
+    /// Everything each level of this weapon can add. See `WeaponUpgrade::after` for how they branch.
+    pub upgrades: Vec<WeaponUpgrade>,
+}
+
+/// How a weapon fires each time it's ready.
+#[derive(Serialize, Deserialize)]
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum WeaponFiring {
+    /// `count` projectiles spread evenly over `spread` radians.
+    Projectiles { count: usize, spread: f32 },
+    /// Splits the player's ranged damage and pierce between 5, 7 or 9 pellets,
+    /// depending on how much there is to go around, plus `extra_pellets`.
+    /// The gaps between pellets are multiplied by `spread_multiplier`.
+    Burst { extra_pellets: usize, spread_multiplier: f32 },
+    /// Straight away hits everything along a line `length` long and `width` wide, up to its pierce.
+    Beam { length: f32, width: f32 },
+    /// `count` blades that circle the player `distance` away, `spin` radians a second, until their lifetime is up.
+    /// They can hit each enemy once every time round, and never run out of pierce.
+    Orbit { count: usize, distance: f32, spin: f32 },
+}
+
+#[derive(Serialize, Deserialize)]
+#[derive(Debug, Copy, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct WeaponSplash {
+    /// Splash damage is the player's ranged damage times this.
+    pub damage_multiplier: f32,
+    pub range: f32,
+    #[serde(default)]
+    pub linger: Option<SplashLinger>,
+}
+
+/// A pool left behind by a splash, which hurts everything in it every half a second.
+#[derive(Serialize, Deserialize)]
+#[derive(Debug, Copy, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct SplashLinger {
+    pub duration: f32,
+    /// Each pulse does this much of the splash damage.
+    pub damage_fraction: f32,
+}
+
+/// One level of a weapon, which the player can pick between waves.
+#[derive(Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct WeaponUpgrade {
+    pub name: String,
+    /// The upgrade this one follows on from, or `None` for the first level.
+    /// Upgrades that follow on from the same one are branches: taking one rules out the others.
+    #[serde(default)]
+    pub after: Option<String>,
+    pub effects: Vec<UpgradeEffect>,
+}
+
+/// What a weapon upgrade changes. The ones that take an `f32` multiply what's already there.
+#[derive(Serialize, Deserialize)]
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum UpgradeEffect {
+    Damage(f32),
+    Cooldown(f32),
+    Speed(f32),
+    Pierce(usize),
+    /// Also widens beams.
+    Radius(f32),
+    /// More projectiles, pellets or blades.
+    Projectiles(usize),
+    /// How far apart projectiles and pellets are.
+    Spread(f32),
+    Lifetime(f32),
+    /// How long beams are and how far out blades orbit.
+    Reach(f32),
+    SplashDamage(f32),
+    SplashRange(f32),
+    /// Splashes leave a pool behind.
+    Linger(SplashLinger),
+    /// Adds these modifiers, replacing any of the same kind the weapon already has.
+    Modifiers(ProjectileModifiers),
+}
+
+/// Extra behaviours for a weapon's projectiles, which can be combined however you like.
+#[derive(Serialize, Deserialize)]
+#[derive(Debug, Copy, Clone, PartialEq, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct ProjectileModifiers {
+    pub seeking: Option<SeeksEnemies>,
+    pub ricochet: Option<Ricochet>,
+    pub chain: Option<ChainLightning>,
+    pub boomerang: Option<Boomerang>,
+    pub split: Option<SplitOnHit>,
+}
+
+/// The cards offered after each wave, of which the player picks one.
+#[derive(Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct DraftBalance {
+    pub cards_per_draft: usize,
+    /// Redrawing every card on offer.
+    pub rerolls_per_run: usize,
+    /// Taking a card out of the pool for the rest of the run, and drawing another in its place.
+    pub banishes_per_run: usize,
+    /// How likely each card drawn is to be common, rare or legendary, relative to each other.
+    pub rarity_weights: [f32; 3],
+    pub cards: Vec<UpgradeCard>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct UpgradeCard {
+    pub name: String,
+    pub rarity: Rarity,
+    pub effect: CardEffect,
+    /// How many times it can be picked in a run. It stops being drawn after that.
+    #[serde(default)]
+    pub max_picks: Option<usize>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Rarity {
+    Common,
+    Rare,
+    Legendary,
+}
+
+/// What picking a card does.
+#[derive(Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq)]
+pub enum CardEffect {
+    /// The health, attack and speed upgrades, using the numbers in `upgrades`.
+    Health,
+    Attack,
+    Speed,
+    MaxHealth(usize),
+    CloseAttackDamage(usize),
+    EndOfRoundHeal(usize),
+    /// Heals this much for every kill.
+    Lifesteal(usize),
+    /// Heals this much every second.
+    Regeneration(f32),
+    /// Power-ups are picked up from this much further away.
+    Magnet(f32),
+    /// The shockwave goes off by itself whenever it's ready and an enemy is in reach.
+    AutoShockwave,
+    /// Applies to every weapon, on top of its own upgrades.
+    Mutation(Vec<UpgradeEffect>),
+}
+
+#[derive(Serialize, Deserialize)]
+#[derive(Debug, Copy, Clone, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct PowerUpBalance {
+    pub spawn_interval: f32,
+    pub spawn_distance_min: f32,
+    pub spawn_distance_max: f32,
+    /// Heals `max_hp / heal_divisor`.
+    pub heal_divisor: usize,
+    pub boost_duration: f32,
+    pub boost_speed_multiplier: f32,
+}
+
+/// How a run's score adds up, on top of the points for each kill
+/// and for calling waves early.
+#[derive(Serialize, Deserialize)]
+#[derive(Debug, Copy, Clone, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct ScoreBalance {
+    pub per_wave: u64,
+    pub per_second: f32,
+    /// Taken off for every point of damage the player takes, but the score never goes below 0.
+    pub per_damage_taken: f32,
+}
+
+impl BalanceSource {
+    pub fn new(path: PathBuf) -> Self {
+        let last_modified = std::fs::metadata(&path).and_then(|metadata| metadata.modified()).ok();
+        Self { path, last_modified, time_until_check: 0.0 }
+    }
+}
+
+impl Balance {
+    /// A hash of every number in the balance, so a replay can tell whether it's being played back
+    /// with the same balance it was recorded with.
+    /// It's FNV-1a over the balance written out as RON, which doesn't change between builds like `DefaultHasher` can.
+    pub fn fingerprint(&self) -> u64 {
+        let serialized = ron::to_string(self).expect("the balance is always serializable");
+        serialized.bytes().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+            (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+        })
+    }
+
+    /// Checks for numbers that would break the game, rather than just make it hard.
+    /// Returns a description of every problem found.
+    pub fn validate(&self) -> Result<(), Vec<String>> {
+        let mut problems = Vec::new();
+        let mut check = |ok: bool, problem: &str| {
+            if !ok {
+                problems.push(problem.to_string());
+            }
+        };
+
+        let player = &self.player;
+        check(player.health > 0, "player.health must be more than 0");
+        check(player.stats.close_attack_cooldown > 0.0, "player.stats.close_attack_cooldown must be more than 0");
+        check(player.stats.close_attack_radius >= 0.0, "player.stats.close_attack_radius can't be negative");
+        check(player.stats.close_attack_knockback >= 0.0, "player.stats.close_attack_knockback can't be negative");
+        check(player.stats.ranged_attack_cooldown > 0.0, "player.stats.ranged_attack_cooldown must be more than 0");
+        check(player.stats.ranged_attack_speed > 0.0, "player.stats.ranged_attack_speed must be more than 0");
+        check(player.stats.movement_speed >= 0.0, "player.stats.movement_speed can't be negative");
+
+        let waves = &self.waves;
+        check(
+            waves.script.iter()
+                .any(|scripted| scripted.from_wave <= 1 && scripted.to_wave.is_none() && scripted.every == 1),
+            "waves.script needs an entry that covers every wave, from wave 1 on, with no to_wave and an every of 1",
+        );
+        for scripted in &waves.script {
+            check(scripted.every > 0, "every entry in waves.script needs an every of 1 or more");
+            check(
+                scripted.groups.iter().map(|group| group.count).sum::<usize>() > 0,
+                "every entry in waves.script must spawn at least one enemy",
+            );
+            for group in &scripted.groups {
+                let scaling = group.scaling;
+                check(
+                    scaling.health > 0.0 && scaling.damage >= 0.0 && scaling.speed >= 0.0,
+                    "every scaling in waves.script needs more than 0 health, and damage and speed of 0 or more",
+                );
+                let pattern_ok = match group.pattern {
+                    SpawnPattern::Scatter => true,
+                    SpawnPattern::Ring { distance } => distance >= 0.0,
+                    SpawnPattern::Cluster { distance, spread } => distance >= 0.0 && spread >= 0.0,
+                    SpawnPattern::Line { distance, length } => distance >= 0.0 && length >= 0.0,
+                    SpawnPattern::Trickle { interval } => interval >= 0.0,
+                };
+                check(pattern_ok, "spawn patterns in waves.script can't have negative numbers");
+                check(group.delay >= 0.0, "group delays in waves.script can't be negative");
+            }
+        }
+        check(waves.spawn_telegraph >= 0.0, "waves.spawn_telegraph can't be negative");
+        check(waves.intermission >= 0.0, "waves.intermission can't be negative");
+        check(waves.early_call_score_per_second >= 0.0, "waves.early_call_score_per_second can't be negative");
+        check(waves.spawn_distance_min >= 0.0, "waves.spawn_distance_min can't be negative");
+        check(
+            waves.spawn_distance_max_base >= waves.spawn_distance_min,
+            "waves.spawn_distance_max_base can't be less than waves.spawn_distance_min",
+        );
+        check(waves.spawn_distance_max_per_wave >= 0.0, "waves.spawn_distance_max_per_wave can't be negative");
+        check(waves.enemy_health_base + waves.enemy_health_per_wave > 0, "enemies must have some health");
+        check(waves.melee_cooldown_base > 0.0, "waves.melee_cooldown_base must be more than 0");
+        check(waves.melee_cooldown_scaling > 0.0, "waves.melee_cooldown_scaling must be more than 0");
+        check(waves.ranged_attack_cooldown > 0.0, "waves.ranged_attack_cooldown must be more than 0");
+        check(waves.speed_scaling > 0.0, "waves.speed_scaling must be more than 0");
+        check(waves.ranged_damage_scaling > 0.0, "waves.ranged_damage_scaling must be more than 0");
+        for rule in &waves.melee_only_rules {
+            check(
+                (0.0..=1.0).contains(&rule.chance),
+                "every chance in waves.melee_only_rules must be between 0 and 1",
+            );
+        }
+
+        let enemies = &self.enemies;
+        for kind in EnemyKind::ALL {
+            let name = kind.name();
+            let stats = enemies.stats(kind);
+            check(stats.radius > 0.0, &format!("enemies.{name}.radius must be more than 0"));
+            check(stats.health_multiplier > 0.0, &format!("enemies.{name}.health_multiplier must be more than 0"));
+            check(
+                stats.melee_damage_multiplier >= 0.0,
+                &format!("enemies.{name}.melee_damage_multiplier can't be negative"),
+            );
+            check(stats.speed_multiplier >= 0.0, &format!("enemies.{name}.speed_multiplier can't be negative"));
+            check(stats.separation_radius >= 0.0, &format!("enemies.{name}.separation_radius can't be negative"));
+            check(stats.separation_strength >= 0.0, &format!("enemies.{name}.separation_strength can't be negative"));
+        }
+        let bosses = &self.bosses;
+        check(
+            (0.0..=1.0).contains(&bosses.phase_two_below) && (0.0..=1.0).contains(&bosses.phase_three_below),
+            "bosses.phase_two_below and bosses.phase_three_below must be between 0 and 1",
+        );
+        check(bosses.ring_cooldown > 0.0, "bosses.ring_cooldown must be more than 0");
+        check(bosses.ring_bullet_speed >= 0.0, "bosses.ring_bullet_speed can't be negative");
+        check(bosses.ring_damage_multiplier >= 0.0, "bosses.ring_damage_multiplier can't be negative");
+        check(bosses.summon_cooldown > 0.0, "bosses.summon_cooldown must be more than 0");
+        check(bosses.charge_cooldown > 0.0, "bosses.charge_cooldown must be more than 0");
+        check(bosses.charge_duration >= 0.0, "bosses.charge_duration can't be negative");
+        check(bosses.charge_speed_multiplier >= 0.0, "bosses.charge_speed_multiplier can't be negative");
+
+        check(enemies.shooter_distance >= 0.0, "enemies.shooter_distance can't be negative");
+        check(enemies.dash_range >= 0.0, "enemies.dash_range can't be negative");
+        check(enemies.dash_speed_multiplier >= 0.0, "enemies.dash_speed_multiplier can't be negative");
+        check(enemies.dash_duration >= 0.0, "enemies.dash_duration can't be negative");
+        check(enemies.dash_cooldown > 0.0, "enemies.dash_cooldown must be more than 0");
+        check(enemies.split_health_fraction >= 0.0, "enemies.split_health_fraction can't be negative");
+        check(enemies.heal_radius >= 0.0, "enemies.heal_radius can't be negative");
+        check(enemies.heal_fraction >= 0.0, "enemies.heal_fraction can't be negative");
+        check(enemies.heal_cooldown > 0.0, "enemies.heal_cooldown must be more than 0");
+        for composition in &enemies.composition {
+            check(
+                composition.weights().iter().all(|(_, weight)| *weight >= 0.0),
+                "every weight in enemies.composition must be 0 or more",
+            );
+        }
+        for shot in &enemies.shots {
+            let ok = match shot.pattern {
+                ShotPattern::Aimed | ShotPattern::Leading => true,
+                ShotPattern::Fan { count, spread } => count > 0 && spread >= 0.0,
+                ShotPattern::Radial { count } => count > 0,
+                ShotPattern::Homing { speed_multiplier, turn_rate, lifetime } => {
+                    speed_multiplier > 0.0 && turn_rate >= 0.0 && lifetime > 0.0
+                },
+                ShotPattern::Mine { fuse, radius } => fuse >= 0.0 && radius >= 0.0,
+            };
+            check(ok, "every pattern in enemies.shots needs a count of 1 or more, and no negative numbers");
+        }
+
+        let upgrades = &self.upgrades;
+        check(upgrades.health_heal_divisor > 0, "upgrades.health_heal_divisor must be more than 0");
+        check(
+            upgrades.attack_ranged_cooldown_multiplier > 0.0,
+            "upgrades.attack_ranged_cooldown_multiplier must be more than 0",
+        );
+        check(upgrades.speed_movement_multiplier > 0.0, "upgrades.speed_movement_multiplier must be more than 0");
+        check(
+            upgrades.speed_ranged_attack_speed_multiplier > 0.0,
+            "upgrades.speed_ranged_attack_speed_multiplier must be more than 0",
+        );
+
+        let weapons = &self.weapons;
+        check(
+            weapons.roster.iter().any(|weapon| weapon.unlock_wave <= 1),
+            "weapons.roster needs a weapon that's unlocked from the start",
+        );
+        for weapon in &weapons.roster {
+            let name = &weapon.name;
+            check(weapon.cooldown_multiplier > 0.0, &format!("weapon {name}'s cooldown must be more than 0"));
+            check(
+                weapon.damage_multiplier >= 0.0
+                    && weapon.speed_multiplier >= 0.0
+                    && weapon.pierce_multiplier >= 0.0
+                    && weapon.radius >= 0.0,
+                &format!("weapon {name} can't have a negative multiplier or radius"),
+            );
+            check(
+                weapon.lifetime.map_or(true, |lifetime| lifetime > 0.0),
+                &format!("weapon {name}'s lifetime must be more than 0"),
+            );
+            check(
+                weapon.splash.map_or(true, |splash| splash.damage_multiplier >= 0.0 && splash.range >= 0.0),
+                &format!("weapon {name}'s splash can't have negative numbers"),
+            );
+            check(
+                weapon.modifiers.has_no_negative_numbers(),
+                &format!("weapon {name}'s modifiers can't have negative numbers"),
+            );
+            let firing_ok = match weapon.firing {
+                WeaponFiring::Projectiles { count, spread } => count > 0 && spread >= 0.0,
+                WeaponFiring::Burst { spread_multiplier, .. } => spread_multiplier >= 0.0,
+                WeaponFiring::Beam { length, width } => length >= 0.0 && width >= 0.0,
+                WeaponFiring::Orbit { count, distance, .. } => {
+                    count > 0 && distance >= 0.0 && weapon.lifetime.is_some()
+                }
+            };
+            check(
+                firing_ok,
+                &format!("weapon {name}'s firing needs a count of at least 1, no negatives, and a lifetime for Orbit"),
+            );
+            check(
+                weapon.splash.and_then(|splash| splash.linger).map_or(true, |linger| linger.is_valid()),
+                &format!("weapon {name}'s linger needs a duration and damage_fraction that aren't negative"),
+            );
+            for (index, upgrade) in weapon.upgrades.iter().enumerate() {
+                let earlier = &weapon.upgrades[..index];
+                check(
+                    !earlier.iter().any(|other| other.name == upgrade.name),
+                    &format!("weapon {name} has more than one upgrade called {}", upgrade.name),
+                );
+                check(
+                    upgrade.after.as_ref().map_or(true, |after| earlier.iter().any(|other| &other.name == after)),
+                    &format!("weapon {name}'s upgrade {} has to come after one listed before it", upgrade.name),
+                );
+                check(
+                    upgrade.effects.iter().all(UpgradeEffect::is_valid),
+                    &format!("weapon {name}'s upgrade {} can't have negative numbers", upgrade.name),
+                );
+            }
+        }
+        check(
+            weapons.small_burst_max_total <= weapons.medium_burst_max_total,
+            "weapons.small_burst_max_total can't be more than weapons.medium_burst_max_total",
+        );
+
+        let power_ups = &self.power_ups;
+        check(power_ups.spawn_interval > 0.0, "power_ups.spawn_interval must be more than 0");
+        check(
+            power_ups.spawn_distance_max >= power_ups.spawn_distance_min,
+            "power_ups.spawn_distance_max can't be less than power_ups.spawn_distance_min",
+        );
+        check(power_ups.heal_divisor > 0, "power_ups.heal_divisor must be more than 0");
+        check(power_ups.boost_duration >= 0.0, "power_ups.boost_duration can't be negative");
+
+        let draft = &self.draft;
+        check(draft.cards_per_draft > 0, "draft.cards_per_draft must be more than 0");
+        check(draft.rarity_weights.iter().all(|weight| *weight >= 0.0), "draft.rarity_weights can't be negative");
+        for card in &draft.cards {
+            check(
+                draft.weight(card.rarity) > 0.0,
+                &format!("draft card {} can never be drawn, since its rarity's weight is 0", card.name),
+            );
+            let valid = match &card.effect {
+                CardEffect::Regeneration(amount) | CardEffect::Magnet(amount) => *amount >= 0.0,
+                CardEffect::Mutation(effects) => effects.iter().all(UpgradeEffect::is_valid),
+                _ => true,
+            };
+            check(valid, &format!("draft card {} can't have negative numbers", card.name));
+        }
+
+        let score = &self.score;
+        check(score.per_second >= 0.0, "score.per_second can't be negative");
+        check(score.per_damage_taken >= 0.0, "score.per_damage_taken can't be negative");
+
+        if problems.is_empty() { Ok(()) } else { Err(problems) }
+    }
+
+    pub fn enemy_health(&self, kind: EnemyKind, wave: isize) -> usize {
+        let health = self.waves.enemy_health(wave) as f32 * self.enemies.stats(kind).health_multiplier;
+        (health as usize).max(1)
+    }
+}
+
+impl WaveBalance {
+    /// The last entry in the script that covers `wave`.
+    pub fn scripted_wave(&self, wave: isize) -> Option<&ScriptedWave> {
+        self.script.iter().filter(|scripted| scripted.covers(wave)).last()
+    }
+
+    pub fn spawn_distance_max(&self, wave: isize) -> f32 {
+        self.spawn_distance_max_base + self.spawn_distance_max_per_wave * wave as f32
+    }
+
+    pub fn enemy_health(&self, wave: isize) -> usize {
+        self.enemy_health_base + self.enemy_health_per_wave * wave as usize
+    }
+
+    pub fn melee_damage(&self, wave: isize) -> usize {
+        self.melee_damage_base + self.melee_damage_per_wave * wave as usize
+    }
+
+    pub fn melee_cooldown(&self, wave: isize) -> f32 {
+        self.melee_cooldown_base * f32::powi(self.melee_cooldown_scaling, (wave - 1) as i32)
+    }
+
+    pub fn speed_multiplier(&self, wave: isize) -> f32 {
+        f32::powi(self.speed_scaling, wave as i32)
+    }
+
+    /// Rolls whether an enemy in this wave is a shooter.
+    /// A chance of 1 is taken without rolling at all.
+    pub fn rolls_shooter(&self, wave: isize, rng: &mut impl rand::Rng) -> bool {
+        for rule in &self.melee_only_rules {
+            if wave < rule.before_wave && (rule.chance >= 1.0 || rng.gen::<f32>() < rule.chance) {
+                return false;
+            }
+        }
+        wave < self.melee_only_from_wave && self.ranged_damage(wave) > 0
+    }
+
+    pub fn ranged_damage(&self, wave: isize) -> usize {
+        (wave - self.ranged_damage_wave_offset).max(0) as usize
+            * f32::powi(self.ranged_damage_scaling, (wave - 10) as i32) as usize
+    }
+}
+
+impl EnemyBalance {
+    pub fn stats(&self, kind: EnemyKind) -> EnemyKindStats {
+        match kind {
+            EnemyKind::Chaser => self.chaser,
+            EnemyKind::Shooter => self.shooter,
+            EnemyKind::Dasher => self.dasher,
+            EnemyKind::Brute => self.brute,
+            EnemyKind::Splitter => self.splitter,
+            EnemyKind::Healer => self.healer,
+            EnemyKind::Boss => self.boss,
+        }
+    }
+
+    #[cfg(test)]
+    pub fn stats_mut(&mut self, kind: EnemyKind) -> &mut EnemyKindStats {
+        match kind {
+            EnemyKind::Chaser => &mut self.chaser,
+            EnemyKind::Shooter => &mut self.shooter,
+            EnemyKind::Dasher => &mut self.dasher,
+            EnemyKind::Brute => &mut self.brute,
+            EnemyKind::Splitter => &mut self.splitter,
+            EnemyKind::Healer => &mut self.healer,
+            EnemyKind::Boss => &mut self.boss,
+        }
+    }
+
+    /// What an enemy of `kind` fires in `wave`, if it shoots at all.
+    pub fn shot_pattern(&self, kind: EnemyKind, wave: isize) -> Option<ShotPattern> {
+        self.shots.iter()
+            .filter(|shot| shot.kind == kind && shot.from_wave <= wave)
+            .last()
+            .map(|shot| shot.pattern)
+    }
+
+    /// Picks the kind of an enemy that isn't a shooter, weighted by the wave's composition.
+    /// A composition with only one kind in it is taken without rolling at all.
+    pub fn pick_kind(&self, wave: isize, rng: &mut impl rand::Rng) -> EnemyKind {
+        let Some(composition) = self.composition.iter().filter(|composition| composition.from_wave <= wave).last()
+        else {
+            return EnemyKind::Chaser;
+        };
+        let weights = composition.weights().into_iter()
+            .filter(|(_, weight)| *weight > 0.0)
+            .collect::<Vec<_>>();
+        match weights.as_slice() {
+            [] => return EnemyKind::Chaser,
+            [(kind, _)] => return *kind,
+            _ => {},
+        }
+
+        let total = weights.iter().map(|(_, weight)| weight).sum::<f32>();
+        let mut roll = rng.gen::<f32>() * total;
+        for (kind, weight) in &weights {
+            if roll < *weight {
+                return *kind;
+            }
+            roll -= weight;
+        }
+        // Only reachable through rounding, when the roll lands right at the end.
+        weights[weights.len() - 1].0
+    }
+}
+
+impl DraftBalance {
+    fn weight(&self, rarity: Rarity) -> f32 {
+        self.rarity_weights[rarity as usize]
+    }
+
+    /// Draws a card, leaving out any that `allowed` says no to.
+    /// The rarity is rolled first, out of the rarities that have any cards left to draw.
+    pub fn draw(&self, allowed: impl Fn(usize) -> bool, rng: &mut impl rand::Rng) -> Option<usize> {
+        let candidates = (0..self.cards.len()).filter(|index| allowed(*index)).collect::<Vec<_>>();
+        let rarities = [Rarity::Common, Rarity::Rare, Rarity::Legendary].into_iter()
+            .zip(self.rarity_weights)
+            .filter(|(rarity, weight)| {
+                *weight > 0.0 && candidates.iter().any(|index| self.cards[*index].rarity == *rarity)
+            })
+            .collect::<Vec<_>>();
+
+        let total = rarities.iter().map(|(_, weight)| weight).sum::<f32>();
+        let mut roll = rng.gen::<f32>() * total;
+        let (rarity, _) = *rarities.iter()
+            .find(|(_, weight)| {
+                roll -= weight;
+                roll < 0.0
+            })
+            // Only reachable through rounding, when the roll lands right at the end.
+            .or(rarities.last())?;
+
+        let cards = candidates.into_iter()
+            .filter(|index| self.cards[*index].rarity == rarity)
+            .collect::<Vec<_>>();
+        Some(cards[rng.gen_range(0..cards.len())])
+    }
+}
+
+impl UpgradeCard {
+    /// A short description for the card, like "Heals 2 for every kill".
+    pub fn describe(&self, upgrades: &UpgradeBalance) -> String {
+        match &self.effect {
+            CardEffect::Health => format!(
+                "+{} max health, heals a bit and +{} end of round heal",
+                upgrades.health_max_hp,
+                upgrades.health_end_of_round_heal,
+            ),
+            CardEffect::Attack => format!(
+                "+{} ranged damage, {:+.0}% cooldown and +{} shockwave damage",
+                upgrades.attack_ranged_damage,
+                (upgrades.attack_ranged_cooldown_multiplier - 1.0) * 100.0,
+                upgrades.attack_close_damage,
+            ),
+            CardEffect::Speed => format!(
+                "{:+.0}% movement speed and {:+.0}% projectile speed",
+                (upgrades.speed_movement_multiplier - 1.0) * 100.0,
+                (upgrades.speed_ranged_attack_speed_multiplier - 1.0) * 100.0,
+            ),
+            CardEffect::MaxHealth(amount) => format!("+{amount} max health"),
+            CardEffect::CloseAttackDamage(amount) => format!("+{amount} shockwave damage"),
+            CardEffect::EndOfRoundHeal(amount) => format!("+{amount} end of round heal"),
+            CardEffect::Lifesteal(amount) => format!("Heals {amount} for every kill"),
+            CardEffect::Regeneration(amount) => format!("Heals {amount} every second"),
+            CardEffect::Magnet(range) => format!("Picks up power-ups from {range} further away"),
+            CardEffect::AutoShockwave => "The shockwave goes off by itself when enemies get close".to_string(),
+            CardEffect::Mutation(effects) => {
+                let effects = effects.iter().map(UpgradeEffect::describe).collect::<Vec<_>>();
+                format!("Every weapon: {}", effects.join(", "))
+            },
+        }
+    }
+}
+
+impl ScriptedWave {
+    pub fn covers(&self, wave: isize) -> bool {
+        wave >= self.from_wave
+            && self.to_wave.map_or(true, |to_wave| wave <= to_wave)
+            && (wave - self.from_wave) % self.every.max(1) == 0
+    }
+}
+
+impl SpawnGroup {
+    /// How many enemies there are in the group `waves_in` waves after the script entry's `from_wave`.
+    pub fn count(&self, waves_in: isize) -> usize {
+        self.count + self.count_per_wave * waves_in.max(0) as usize
+    }
+}
+
+impl BossBalance {
+    /// Which phase a boss with `health_left` (from 0 to 1) of its health is in, from 1 to 3.
+    pub fn phase(&self, health_left: f32) -> usize {
+        if health_left < self.phase_three_below {
+            3
+        } else if health_left < self.phase_two_below {
+            2
+        } else {
+            1
+        }
+    }
+}
+
+impl WaveComposition {
+    pub fn weights(&self) -> [(EnemyKind, f32); 5] {
+        [
+            (EnemyKind::Chaser, self.chaser),
+            (EnemyKind::Dasher, self.dasher),
+            (EnemyKind::Brute, self.brute),
+            (EnemyKind::Splitter, self.splitter),
+            (EnemyKind::Healer, self.healer),
+        ]
+    }
+}
+
+impl WeaponBalance {
+    /// If the roster has been cut short since the weapon was picked, the last weapon in it is used instead.
+    pub fn definition(&self, weapon: PlayerWeapon) -> &WeaponDefinition {
+        &self.roster[weapon.0.min(self.roster.len() - 1)]
+    }
+
+    pub fn is_unlocked(&self, weapon: PlayerWeapon, wave: isize) -> bool {
+        self.roster.get(weapon.0).is_some_and(|definition| definition.unlock_wave <= wave.max(1))
+    }
+
+    /// The next unlocked weapon after `weapon` in the roster, or before it if going `backwards`.
+    pub fn cycle(&self, weapon: PlayerWeapon, wave: isize, backwards: bool) -> PlayerWeapon {
+        let count = self.roster.len();
+        (1..=count)
+            .map(|step| PlayerWeapon((weapon.0 + if backwards { count - step } else { step }) % count))
+            .find(|next| self.is_unlocked(*next, wave))
+            .unwrap_or(weapon)
+    }
+}
+
+impl WeaponDefinition {
+    pub fn damage(&self, ranged_damage: usize) -> usize {
+        (ranged_damage as f32 * self.damage_multiplier + self.damage_bonus as f32).max(0.0) as usize
+    }
+
+    pub fn pierce(&self, ranged_pierce: usize) -> usize {
+        (ranged_pierce as f32 * self.pierce_multiplier) as usize + self.pierce_bonus
+    }
+
+    /// The upgrades that can be taken next, after the ones in `taken`.
+    pub fn next_upgrades(&self, taken: &[usize]) -> Vec<usize> {
+        let last = taken.last().and_then(|index| self.upgrades.get(*index)).map(|upgrade| &upgrade.name);
+        (0..self.upgrades.len())
+            .filter(|index| self.upgrades[*index].after.as_ref() == last)
+            .collect()
+    }
+
+    /// This weapon with the upgrades in `taken` applied, in order.
+    pub fn upgraded(&self, taken: &[usize]) -> WeaponDefinition {
+        let mut weapon = self.clone();
+        let effects = taken.iter()
+            .filter_map(|index| self.upgrades.get(*index))
+            .flat_map(|upgrade| &upgrade.effects);
+        for effect in effects {
+            weapon.apply(*effect);
+        }
+        weapon
+    }
+
+    /// This weapon with `effects` applied on top, in order.
+    pub fn with_effects(mut self, effects: &[UpgradeEffect]) -> WeaponDefinition {
+        for effect in effects {
+            self.apply(*effect);
+        }
+        self
+    }
+
+    fn apply(&mut self, effect: UpgradeEffect) {
+        match effect {
+            UpgradeEffect::Damage(multiplier) => self.damage_multiplier *= multiplier,
+            UpgradeEffect::Cooldown(multiplier) => self.cooldown_multiplier *= multiplier,
+            UpgradeEffect::Speed(multiplier) => self.speed_multiplier *= multiplier,
+            UpgradeEffect::Pierce(extra) => self.pierce_bonus += extra,
+            UpgradeEffect::Radius(multiplier) => {
+                self.radius *= multiplier;
+                if let WeaponFiring::Beam { width, .. } = &mut self.firing {
+                    *width *= multiplier;
+                }
+            },
+            UpgradeEffect::Projectiles(extra) => match &mut self.firing {
+                WeaponFiring::Projectiles { count, .. } | WeaponFiring::Orbit { count, .. } => *count += extra,
+                WeaponFiring::Burst { extra_pellets, .. } => *extra_pellets += extra,
+                WeaponFiring::Beam { .. } => {},
+            },
+            UpgradeEffect::Spread(multiplier) => match &mut self.firing {
+                WeaponFiring::Projectiles { spread, .. } => *spread *= multiplier,
+                WeaponFiring::Burst { spread_multiplier, .. } => *spread_multiplier *= multiplier,
+                _ => {},
+            },
+            UpgradeEffect::Lifetime(multiplier) => {
+                if let Some(lifetime) = &mut self.lifetime {
+                    *lifetime *= multiplier;
+                }
+            },
+            UpgradeEffect::Reach(multiplier) => match &mut self.firing {
+                WeaponFiring::Beam { length, .. } => *length *= multiplier,
+                WeaponFiring::Orbit { distance, .. } => *distance *= multiplier,
+                _ => {},
+            },
+            UpgradeEffect::SplashDamage(multiplier) => {
+                if let Some(splash) = &mut self.splash {
+                    splash.damage_multiplier *= multiplier;
+                }
+            },
+            UpgradeEffect::SplashRange(multiplier) => {
+                if let Some(splash) = &mut self.splash {
+                    splash.range *= multiplier;
+                }
+            },
+            UpgradeEffect::Linger(linger) => {
+                if let Some(splash) = &mut self.splash {
+                    splash.linger = Some(linger);
+                }
+            },
+            UpgradeEffect::Modifiers(modifiers) => self.modifiers.add(modifiers),
+        }
+    }
+
+    pub fn color(&self) -> Color {
+        let (red, green, blue) = self.color;
+        Color::srgb(red, green, blue)
+    }
+}
+
+impl ProjectileModifiers {
+    fn add(&mut self, other: ProjectileModifiers) {
+        self.seeking = other.seeking.or(self.seeking);
+        self.ricochet = other.ricochet.or(self.ricochet);
+        self.chain = other.chain.or(self.chain);
+        self.boomerang = other.boomerang.or(self.boomerang);
+        self.split = other.split.or(self.split);
+    }
+
+    /// The names of the modifiers that are set, like "seeking".
+    fn names(&self) -> Vec<&'static str> {
+        [
+            ("seeking", self.seeking.is_some()),
+            ("ricochet", self.ricochet.is_some()),
+            ("chain lightning", self.chain.is_some()),
+            ("boomerang", self.boomerang.is_some()),
+            ("split on hit", self.split.is_some()),
+        ].into_iter().filter_map(|(name, set)| set.then_some(name)).collect()
+    }
+
+    fn has_no_negative_numbers(&self) -> bool {
+        let seeking = self.seeking.map_or(true, |seeking| seeking.turn_rate >= 0.0 && seeking.range >= 0.0);
+        let ricochet = self.ricochet.map_or(true, |ricochet| ricochet.range >= 0.0);
+        let chain = self.chain.map_or(true, |chain| chain.range >= 0.0 && chain.damage_fraction >= 0.0);
+        let boomerang = self.boomerang.map_or(true, |boomerang| boomerang.return_after >= 0.0);
+        let split = self.split.map_or(true, |split| split.spread >= 0.0 && split.damage_fraction >= 0.0);
+        seeking && ricochet && chain && boomerang && split
+    }
+}
+
+impl UpgradeEffect {
+    /// A short description for the upgrade screen, like "+20% damage".
+    pub fn describe(&self) -> String {
+        fn change(multiplier: f32, what: &str) -> String {
+            format!("{:+.0}% {what}", (multiplier - 1.0) * 100.0)
+        }
+        match self {
+            Self::Damage(multiplier) => change(*multiplier, "damage"),
+            Self::Cooldown(multiplier) => change(*multiplier, "cooldown"),
+            Self::Speed(multiplier) => change(*multiplier, "projectile speed"),
+            Self::Pierce(extra) => format!("+{extra} pierce"),
+            Self::Radius(multiplier) => change(*multiplier, "size"),
+            Self::Projectiles(extra) => format!("+{extra} projectiles"),
+            Self::Spread(multiplier) => change(*multiplier, "spread"),
+            Self::Lifetime(multiplier) => change(*multiplier, "range"),
+            Self::Reach(multiplier) => change(*multiplier, "reach"),
+            Self::SplashDamage(multiplier) => change(*multiplier, "splash damage"),
+            Self::SplashRange(multiplier) => change(*multiplier, "splash size"),
+            Self::Linger(linger) => format!(
+                "splashes leave a pool for {}s that does {:.0}% of their damage",
+                linger.duration,
+                linger.damage_fraction * 100.0,
+            ),
+            Self::Modifiers(modifiers) => format!("adds {}", modifiers.names().join(" and ")),
+        }
+    }
+
+    fn is_valid(&self) -> bool {
+        match self {
+            Self::Damage(multiplier)
+            | Self::Speed(multiplier)
+            | Self::Radius(multiplier)
+            | Self::Spread(multiplier)
+            | Self::Lifetime(multiplier)
+            | Self::Reach(multiplier)
+            | Self::SplashDamage(multiplier)
+            | Self::SplashRange(multiplier) => *multiplier >= 0.0,
+            Self::Cooldown(multiplier) => *multiplier > 0.0,
+            Self::Pierce(_) | Self::Projectiles(_) => true,
+            Self::Linger(linger) => linger.is_valid(),
+            Self::Modifiers(modifiers) => modifiers.has_no_negative_numbers(),
+        }
+    }
+}
+
+impl SplashLinger {
+    fn is_valid(&self) -> bool {
+        self.duration >= 0.0 && self.damage_fraction >= 0.0
+    }
+}
+
+impl Default for PlayerBalance {
+    fn default() -> Self {
+        Self {
+            health: 100,
+            stats: PlayerStats::default(),
+        }
+    }
+}
+
+impl Default for WaveBalance {
+    fn default() -> Self {
+        Self {
+            // The same as `8 + 2 * wave` enemies every wave, in two halves a few seconds apart,
+            // with a boss on its own every 10th wave.
+            script: vec![
+                ScriptedWave {
+                    from_wave: 1,
+                    groups: vec![
+                        SpawnGroup { count: 6, count_per_wave: 1, ..default() },
+                        SpawnGroup { count: 4, count_per_wave: 1, delay: 4.0, ..default() },
+                    ],
+                    ..default()
+                },
+                ScriptedWave {
+                    from_wave: 10,
+                    every: 10,
+                    groups: vec![SpawnGroup {
+                        kind: GroupKind::Only(EnemyKind::Boss),
+                        count: 1,
+                        pattern: SpawnPattern::Ring { distance: 400.0 },
+                        ..default()
+                    }],
+                    ..default()
+                },
+            ],
+            spawn_telegraph: 0.75,
+            intermission: 3.0,
+            early_call_score_per_second: 10.0,
+
+            spawn_distance_min: 400.0,
+            spawn_distance_max_base: 800.0,
+            spawn_distance_max_per_wave: 20.0,
+
+            enemy_health_base: 24,
+            enemy_health_per_wave: 2,
+
+            melee_damage_base: 9,
+            melee_damage_per_wave: 1,
+            melee_cooldown_base: 1.0,
+            melee_cooldown_scaling: 0.95,
+
+            movement_speed_base: 75.0,
+            ranged_attack_speed_base: 200.0,
+            speed_scaling: 1.05,
+
+            ranged_attack_cooldown: 1.5,
+            ranged_damage_wave_offset: 5,
+            ranged_damage_scaling: 1.05,
+
+            melee_only_rules: vec![
+                MeleeOnlyRule { before_wave: 5, chance: 1.0 },
+                MeleeOnlyRule { before_wave: 10, chance: 0.9 },
+                // Waves before 20 get a second roll from the next rule when this one fails.
+                MeleeOnlyRule { before_wave: 25, chance: 0.8 },
+                MeleeOnlyRule { before_wave: 20, chance: 0.6 },
+            ],
+            melee_only_from_wave: 21,
+        }
+    }
+}
+
+impl Default for EnemyBalance {
+    fn default() -> Self {
+        Self {
+            chaser: EnemyKindStats {
+                radius: 40.0,
+                health_multiplier: 1.0,
+                melee_damage_multiplier: 1.0,
+                speed_multiplier: 1.0,
+                separation_radius: 110.0,
+                separation_strength: 1.5,
+                score: 10,
+            },
+            shooter: EnemyKindStats {
+                radius: 35.0,
+                health_multiplier: 0.8,
+                melee_damage_multiplier: 0.5,
+                speed_multiplier: 0.9,
+                separation_radius: 110.0,
+                separation_strength: 1.5,
+                score: 15,
+            },
+            dasher: EnemyKindStats {
+                radius: 30.0,
+                health_multiplier: 0.6,
+                melee_damage_multiplier: 0.8,
+                speed_multiplier: 1.3,
+                separation_radius: 90.0,
+                separation_strength: 1.0,
+                score: 12,
+            },
+            brute: EnemyKindStats {
+                radius: 60.0,
+                health_multiplier: 3.0,
+                melee_damage_multiplier: 1.5,
+                speed_multiplier: 0.6,
+                separation_radius: 150.0,
+                separation_strength: 2.0,
+                score: 30,
+            },
+            splitter: EnemyKindStats {
+                radius: 45.0,
+                health_multiplier: 1.2,
+                melee_damage_multiplier: 1.0,
+                speed_multiplier: 0.9,
+                separation_radius: 115.0,
+                separation_strength: 1.5,
+                score: 20,
+            },
+            healer: EnemyKindStats {
+                radius: 35.0,
+                health_multiplier: 0.8,
+                melee_damage_multiplier: 0.5,
+                speed_multiplier: 0.8,
+                separation_radius: 140.0,
+                separation_strength: 2.0,
+                score: 25,
+            },
+            // Bosses are on their own, apart from what they summon, so they don't need to keep their distance.
+            boss: EnemyKindStats {
+                radius: 120.0,
+                health_multiplier: 25.0,
+                melee_damage_multiplier: 2.0,
+                speed_multiplier: 0.5,
+                separation_radius: 0.0,
+                separation_strength: 0.0,
+                score: 500,
+            },
+
+            shooter_distance: 350.0,
+
+            dash_range: 300.0,
+            dash_speed_multiplier: 4.0,
+            dash_duration: 0.35,
+            dash_cooldown: 2.5,
+
+            split_count: 3,
+            split_health_fraction: 0.4,
+
+            heal_radius: 200.0,
+            heal_fraction: 0.1,
+            heal_cooldown: 2.0,
+
+            composition: vec![
+                WaveComposition { from_wave: 1, chaser: 1.0, ..default() },
+                WaveComposition { from_wave: 3, chaser: 0.7, dasher: 0.3, ..default() },
+                WaveComposition { from_wave: 6, chaser: 0.5, dasher: 0.2, brute: 0.15, splitter: 0.15, ..default() },
+                WaveComposition { from_wave: 10, chaser: 0.4, dasher: 0.2, brute: 0.15, splitter: 0.15, healer: 0.1 },
+            ],
+
+            shots: vec![
+                ShotRule { kind: EnemyKind::Shooter, from_wave: 1, pattern: ShotPattern::Aimed },
+                ShotRule { kind: EnemyKind::Shooter, from_wave: 8, pattern: ShotPattern::Leading },
+                ShotRule {
+                    kind: EnemyKind::Shooter,
+                    from_wave: 13,
+                    pattern: ShotPattern::Fan { count: 3, spread: 0.4 },
+                },
+                ShotRule {
+                    kind: EnemyKind::Shooter,
+                    from_wave: 18,
+                    pattern: ShotPattern::Fan { count: 5, spread: 0.7 },
+                },
+                ShotRule {
+                    kind: EnemyKind::Healer,
+                    from_wave: 12,
+                    pattern: ShotPattern::Homing { speed_multiplier: 0.5, turn_rate: 1.5, lifetime: 6.0 },
+                },
+                ShotRule { kind: EnemyKind::Brute, from_wave: 15, pattern: ShotPattern::Radial { count: 6 } },
+                ShotRule {
+                    kind: EnemyKind::Dasher,
+                    from_wave: 18,
+                    pattern: ShotPattern::Mine { fuse: 1.5, radius: 90.0 },
+                },
+            ],
+        }
+    }
+}
+
+impl Default for ScriptedWave {
+    fn default() -> Self {
+        Self {
+            from_wave: 1,
+            to_wave: None,
+            every: 1,
+            groups: Vec::new(),
+        }
+    }
+}
+
+impl Default for SpawnGroup {
+    fn default() -> Self {
+        Self {
+            kind: GroupKind::Mixed,
+            count: 0,
+            count_per_wave: 0,
+            pattern: SpawnPattern::Scatter,
+            scaling: StatScaling::default(),
+            delay: 0.0,
+        }
+    }
+}
+
+impl Default for StatScaling {
+    fn default() -> Self {
+        Self { health: 1.0, damage: 1.0, speed: 1.0 }
+    }
+}
+
+impl Default for BossBalance {
+    fn default() -> Self {
+        Self {
+            phase_two_below: 0.66,
+            phase_three_below: 0.33,
+
+            ring_bullet_count: 16,
+            ring_cooldown: 2.5,
+            ring_bullet_speed: 180.0,
+            ring_damage_multiplier: 0.5,
+
+            summon_count: 4,
+            summon_cooldown: 6.0,
+
+            charge_cooldown: 4.0,
+            charge_duration: 0.6,
+            charge_speed_multiplier: 6.0,
+
+            reward_upgrades: 2,
+        }
+    }
+}
+
+impl Default for UpgradeBalance {
+    fn default() -> Self {
+        Self {
+            attack_ranged_damage: 2,
+            attack_ranged_cooldown_multiplier: 0.95,
+            attack_close_damage: 10,
+
+            health_max_hp: 20,
+            health_heal_divisor: 5,
+            health_end_of_round_heal: 4,
+
+            speed_movement_multiplier: 1.15,
+            speed_ranged_attack_speed_multiplier: 1.2,
+        }
+    }
+}
+
+impl Default for WeaponBalance {
+    fn default() -> Self {
+        let weapon = |name: &str, unlock_wave| WeaponDefinition { name: name.to_string(), unlock_wave, ..default() };
+        let upgrade = |name: &str, after: Option<&str>, effects: &[UpgradeEffect]| WeaponUpgrade {
+            name: name.to_string(),
+            after: after.map(str::to_string),
+            effects: effects.to_vec(),
+        };
+        let seeking = ProjectileModifiers {
+            seeking: Some(SeeksEnemies { turn_rate: 3.0, range: 300.0 }),
+            ..default()
+        };
+        let pool = SplashLinger { duration: 3.0, damage_fraction: 0.25 };
+        Self {
+            roster: vec![
+                WeaponDefinition {
+                    // The spread only matters once there's more than one projectile.
+                    firing: WeaponFiring::Projectiles { count: 1, spread: 0.15 },
+                    damage_bonus: -3,
+                    cooldown_multiplier: 1.2,
+                    speed_multiplier: 0.85,
+                    color: (0.604, 0.804, 0.196),
+                    upgrades: vec![
+                        upgrade("Sharper", None, &[UpgradeEffect::Damage(1.25)]),
+                        upgrade("Piercing", Some("Sharper"), &[UpgradeEffect::Pierce(2)]),
+                        upgrade("Twin shot", Some("Sharper"), &[UpgradeEffect::Projectiles(1)]),
+                        upgrade("Rapid fire", Some("Piercing"), &[UpgradeEffect::Cooldown(0.8)]),
+                        upgrade("Seekers", Some("Twin shot"), &[UpgradeEffect::Modifiers(seeking)]),
+                    ],
+                    ..weapon("Normal", 1)
+                },
+                WeaponDefinition {
+                    firing: WeaponFiring::Burst { extra_pellets: 0, spread_multiplier: 1.0 },
+                    cooldown_multiplier: 1.8,
+                    speed_multiplier: 1.2,
+                    radius: 7.5,
+                    color: (0.678, 1.0, 0.184),
+                    upgrades: vec![
+                        upgrade("Loaded shells", None, &[UpgradeEffect::Damage(1.2)]),
+                        upgrade("More pellets", Some("Loaded shells"), &[UpgradeEffect::Projectiles(2)]),
+                        upgrade(
+                            "Tight choke",
+                            Some("Loaded shells"),
+                            &[UpgradeEffect::Spread(0.5), UpgradeEffect::Speed(1.2)],
+                        ),
+                        upgrade("Even more pellets", Some("More pellets"), &[UpgradeEffect::Projectiles(2)]),
+                        upgrade(
+                            "Slugs",
+                            Some("Tight choke"),
+                            &[UpgradeEffect::Pierce(2), UpgradeEffect::Damage(1.2)],
+                        ),
+                    ],
+                    ..weapon("Burst", 3)
+                },
+                WeaponDefinition {
+                    damage_bonus: 2,
+                    cooldown_multiplier: 2.5,
+                    speed_multiplier: 2.0,
+                    pierce_multiplier: 0.0,
+                    radius: 25.5,
+                    color: (0.42, 0.13, 0.659),
+                    splash: Some(WeaponSplash { damage_multiplier: 2.2, range: 100.0, linger: None }),
+                    upgrades: vec![
+                        upgrade("Volatile", None, &[UpgradeEffect::SplashDamage(1.25)]),
+                        upgrade("Big blast", Some("Volatile"), &[UpgradeEffect::SplashRange(1.4)]),
+                        upgrade("Lingering pool", Some("Volatile"), &[UpgradeEffect::Linger(pool)]),
+                        upgrade("Bigger blast", Some("Big blast"), &[UpgradeEffect::SplashRange(1.3)]),
+                        upgrade("Quick lob", Some("Lingering pool"), &[UpgradeEffect::Cooldown(0.75)]),
+                    ],
+                    ..weapon("Splash", 5)
+                },
+                WeaponDefinition {
+                    firing: WeaponFiring::Projectiles { count: 6, spread: 0.6 },
+                    damage_multiplier: 0.8,
+                    cooldown_multiplier: 2.2,
+                    speed_multiplier: 1.1,
+                    pierce_multiplier: 0.25,
+                    radius: 9.0,
+                    color: (0.984, 0.749, 0.141),
+                    lifetime: Some(0.6),
+                    upgrades: vec![
+                        upgrade("Extra shells", None, &[UpgradeEffect::Projectiles(2)]),
+                        upgrade("Wide spray", Some("Extra shells"), &[UpgradeEffect::Spread(1.4)]),
+                        upgrade(
+                            "Long barrel",
+                            Some("Extra shells"),
+                            &[UpgradeEffect::Lifetime(1.5), UpgradeEffect::Speed(1.2)],
+                        ),
+                    ],
+                    ..weapon("Shotgun", 7)
+                },
+                WeaponDefinition {
+                    firing: WeaponFiring::Beam { length: 900.0, width: 12.0 },
+                    damage_multiplier: 1.5,
+                    cooldown_multiplier: 3.0,
+                    pierce_multiplier: 2.0,
+                    color: (1.0, 0.2, 0.3),
+                    upgrades: vec![
+                        upgrade("Focus", None, &[UpgradeEffect::Damage(1.3)]),
+                        upgrade("Long beam", Some("Focus"), &[UpgradeEffect::Reach(1.5)]),
+                        upgrade("Wide beam", Some("Focus"), &[UpgradeEffect::Radius(2.0)]),
+                    ],
+                    ..weapon("Laser", 10)
+                },
+                WeaponDefinition {
+                    firing: WeaponFiring::Orbit { count: 3, distance: 120.0, spin: 4.0 },
+                    damage_multiplier: 0.7,
+                    cooldown_multiplier: 25.0,
+                    radius: 14.0,
+                    color: (0.8, 0.85, 0.9),
+                    lifetime: Some(3.0),
+                    upgrades: vec![
+                        upgrade("Extra blade", None, &[UpgradeEffect::Projectiles(1)]),
+                        upgrade("Long spin", Some("Extra blade"), &[UpgradeEffect::Lifetime(1.5)]),
+                        upgrade("Wide orbit", Some("Extra blade"), &[UpgradeEffect::Reach(1.4)]),
+                    ],
+                    ..weapon("Blades", 13)
+                },
+                WeaponDefinition {
+                    cooldown_multiplier: 20.0,
+                    speed_multiplier: 0.0,
+                    pierce_multiplier: 0.0,
+                    radius: 18.0,
+                    color: (0.918, 0.345, 0.047),
+                    lifetime: Some(10.0),
+                    splash: Some(WeaponSplash { damage_multiplier: 3.0, range: 130.0, linger: None }),
+                    upgrades: vec![
+                        upgrade("Bigger boom", None, &[UpgradeEffect::SplashDamage(1.3)]),
+                        upgrade("Sticky", Some("Bigger boom"), &[UpgradeEffect::Linger(pool)]),
+                        upgrade("Minefield", Some("Bigger boom"), &[UpgradeEffect::Cooldown(0.6)]),
+                    ],
+                    ..weapon("Mines", 16)
+                },
+                WeaponDefinition {
+                    firing: WeaponFiring::Projectiles { count: 3, spread: 0.6 },
+                    damage_multiplier: 0.5,
+                    speed_multiplier: 0.9,
+                    pierce_multiplier: 0.5,
+                    radius: 12.0,
+                    color: (1.0, 0.45, 0.1),
+                    lifetime: Some(0.35),
+                    upgrades: vec![
+                        upgrade("Hotter", None, &[UpgradeEffect::Damage(1.3)]),
+                        upgrade("Longer flame", Some("Hotter"), &[UpgradeEffect::Lifetime(1.4)]),
+                        upgrade(
+                            "Wider cone",
+                            Some("Hotter"),
+                            &[UpgradeEffect::Spread(1.5), UpgradeEffect::Projectiles(2)],
+                        ),
+                    ],
+                    ..weapon("Flamethrower", 19)
+                },
+            ],
+
+            small_burst_max_total: 200,
+            medium_burst_max_total: 600,
+            burst_spreads: [0.15, 0.1, 0.12],
+        }
+    }
+}
+
+impl Default for WeaponDefinition {
+    fn default() -> Self {
+        Self {
+            name: String::new(),
+            unlock_wave: 1,
+            firing: WeaponFiring::Projectiles { count: 1, spread: 0.0 },
+            damage_multiplier: 1.0,
+            damage_bonus: 0,
+            cooldown_multiplier: 1.0,
+            speed_multiplier: 1.0,
+            pierce_multiplier: 1.0,
+            pierce_bonus: 0,
+            radius: 15.0,
+            color: (1.0, 1.0, 1.0),
+            lifetime: None,
+            splash: None,
+            modifiers: ProjectileModifiers::default(),
+            upgrades: Vec::new(),
+        }
+    }
+}
+
+impl Default for DraftBalance {
+    fn default() -> Self {
+        let card = |name: &str, rarity, effect, max_picks| UpgradeCard {
+            name: name.to_string(),
+            rarity,
+            effect,
+            max_picks,
+        };
+        let chain = ChainLightning { arcs: 2, range: 200.0, damage_fraction: 0.5 };
+        let ricochet = Ricochet { bounces: 2, range: 300.0 };
+        Self {
+            cards_per_draft: 3,
+            rerolls_per_run: 3,
+            banishes_per_run: 2,
+            rarity_weights: [0.75, 0.2, 0.05],
+            cards: vec![
+                card("Vitality", Rarity::Common, CardEffect::Health, None),
+                card("Power", Rarity::Common, CardEffect::Attack, None),
+                card("Swiftness", Rarity::Common, CardEffect::Speed, None),
+                card("Thick skin", Rarity::Common, CardEffect::MaxHealth(30), None),
+                card("Heavy hands", Rarity::Common, CardEffect::CloseAttackDamage(20), None),
+                card("Second wind", Rarity::Common, CardEffect::EndOfRoundHeal(10), None),
+                card("Vampirism", Rarity::Rare, CardEffect::Lifesteal(2), Some(3)),
+                card("Regeneration", Rarity::Rare, CardEffect::Regeneration(1.0), Some(3)),
+                card("Magnet", Rarity::Rare, CardEffect::Magnet(150.0), Some(2)),
+                card("Hair trigger", Rarity::Rare, CardEffect::AutoShockwave, Some(1)),
+                card(
+                    "Storm rounds",
+                    Rarity::Legendary,
+                    CardEffect::Mutation(vec![UpgradeEffect::Modifiers(ProjectileModifiers {
+                        chain: Some(chain),
+                        ..default()
+                    })]),
+                    Some(1),
+                ),
+                card(
+                    "Ricochet rounds",
+                    Rarity::Legendary,
+                    CardEffect::Mutation(vec![UpgradeEffect::Modifiers(ProjectileModifiers {
+                        ricochet: Some(ricochet),
+                        ..default()
+                    })]),
+                    Some(1),
+                ),
+                card("Hydra", Rarity::Legendary, CardEffect::Mutation(vec![UpgradeEffect::Projectiles(2)]), Some(1)),
+                card(
+                    "Glass cannon",
+                    Rarity::Legendary,
+                    CardEffect::Mutation(vec![UpgradeEffect::Damage(1.6), UpgradeEffect::Cooldown(1.25)]),
+                    Some(1),
+                ),
+            ],
+        }
+    }
+}
+
+impl Default for ScoreBalance {
+    fn default() -> Self {
+        Self {
+            per_wave: 50,
+            per_second: 1.0,
+            per_damage_taken: 2.0,
+        }
+    }
+}
+
+impl Default for PowerUpBalance {
+    fn default() -> Self {
+        Self {
+            spawn_interval: 4.0,
+            spawn_distance_min: 300.0,
+            spawn_distance_max: 800.0,
+            heal_divisor: 5,
+            boost_duration: 3.0,
+            boost_speed_multiplier: 2.0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_balance_is_valid() {
+        assert_eq!(Balance::default().validate(), Ok(()));
+    }
+
+    #[test]
+    fn draft_card_that_can_never_be_drawn_is_invalid() {
+        let mut balance = Balance::default();
+        balance.draft.rarity_weights = [0.0; 3];
+        assert!(balance.validate().is_err());
+
+        let mut balance = Balance::default();
+        balance.draft.rarity_weights = [1.0, 1.0, 0.0];
+        let legendary = balance.draft.cards.iter()
+            .find(|card| card.rarity == Rarity::Legendary)
+            .expect("the default draft has a legendary card")
+            .name
+            .clone();
+        let problems = balance.validate().expect_err("a legendary card can never be drawn");
+        assert!(problems.iter().any(|problem| problem.contains(&legendary)));
+
+        // Leaving the rarity out is fine when there's nothing of it to draw.
+        balance.draft.cards.retain(|card| card.rarity != Rarity::Legendary);
+        assert!(balance.validate().is_ok());
+    }
+}
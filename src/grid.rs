@@ -0,0 +1,163 @@
+//! A uniform grid over the world, so collision checks only look at things that are nearby
+//! instead of everything in the run.
+//!
+//! It's in the library rather than the game itself so `benches/spatial.rs` can time it.
+
+use bevy::prelude::*;
+use bevy::utils::HashMap;
+
+/// Roughly the size of an enemy, so most things only ever land in a few cells.
+const CELL_SIZE: f32 = 128.0;
+
+#[derive(Debug, Clone, Default)]
+pub struct SpatialGrid {
+    entries: Vec<GridEntry>,
+    /// Indices into `entries` of everything whose bounding box touches each cell.
+    cells: HashMap<IVec2, Vec<usize>>,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct GridEntry {
+    pub entity: Entity,
+    pub position: Vec2,
+    pub radius: f32,
+}
+
+impl SpatialGrid {
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.cells.clear();
+    }
+
+    pub fn insert(&mut self, entity: Entity, position: Vec2, radius: f32) {
+        let index = self.entries.len();
+        self.entries.push(GridEntry { entity, position, radius });
+
+        let (min, max) = cell_range(position, radius);
+        for y in min.y..=max.y {
+            for x in min.x..=max.x {
+                self.cells.entry(IVec2::new(x, y)).or_default().push(index);
+            }
+        }
+    }
+
+    /// Everything whose bounding box might overlap a circle at `position` with `radius`,
+    /// in the order it was inserted, so callers see things in the same order
+    /// they would by looping over a query.
+    /// Callers still have to make their own exact check.
+    pub fn candidates(&self, position: Vec2, radius: f32) -> Vec<GridEntry> {
+        let (min, max) = cell_range(position, radius);
+        let mut indices = Vec::new();
+        for y in min.y..=max.y {
+            for x in min.x..=max.x {
+                if let Some(cell) = self.cells.get(&IVec2::new(x, y)) {
+                    indices.extend_from_slice(cell);
+                }
+            }
+        }
+
+        // Anything big enough to span several cells shows up once per cell.
+        indices.sort_unstable();
+        indices.dedup();
+        indices.into_iter().map(|index| self.entries[index]).collect()
+    }
+
+    /// Everything that might be touched by a circle with `radius` moving from `start` to `end`.
+    pub fn candidates_along(&self, start: Vec2, end: Vec2, radius: f32) -> Vec<GridEntry> {
+        self.candidates(start.lerp(end, 0.5), start.distance(end) / 2.0 + radius)
+    }
+}
+
+/// How far along the path from `start` to `end` (from 0 to 1) a point first comes
+/// within `radius` of `center`, or `None` if it never does.
+/// A path that starts inside the circle touches it straight away.
+pub fn sweep_circle(start: Vec2, end: Vec2, center: Vec2, radius: f32) -> Option<f32> {
+    let path = end - start;
+    let from_center = start - center;
+
+    let c = from_center.length_squared() - radius * radius;
+    if c <= 0.0 {
+        return Some(0.0);
+    }
+
+    let a = path.length_squared();
+    if a == 0.0 {
+        return None;
+    }
+    let b = 2.0 * from_center.dot(path);
+    let discriminant = b * b - 4.0 * a * c;
+    if discriminant < 0.0 {
+        return None;
+    }
+
+    // Starting outside the circle, both roots are on the same side of 0,
+    // and the smaller one is where the path goes in.
+    let t = (-b - discriminant.sqrt()) / (2.0 * a);
+    (0.0..=1.0).contains(&t).then_some(t)
+}
+
+fn cell_range(position: Vec2, radius: f32) -> (IVec2, IVec2) {
+    let min = ((position - radius) / CELL_SIZE).floor().as_ivec2();
+    let max = ((position + radius) / CELL_SIZE).floor().as_ivec2();
+    (min, max)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn path_starting_inside_touches_straight_away() {
+        assert_eq!(sweep_circle(Vec2::new(1.0, 1.0), Vec2::new(50.0, 0.0), Vec2::ZERO, 5.0), Some(0.0));
+    }
+
+    #[test]
+    fn path_going_through_touches_where_it_enters() {
+        let along = sweep_circle(Vec2::new(-10.0, 0.0), Vec2::new(10.0, 0.0), Vec2::ZERO, 5.0);
+        assert_eq!(along, Some(0.25));
+    }
+
+    #[test]
+    fn grazing_path_touches_at_the_closest_point() {
+        let along = sweep_circle(Vec2::new(-10.0, 5.0), Vec2::new(10.0, 5.0), Vec2::ZERO, 5.0);
+        assert_eq!(along, Some(0.5));
+    }
+
+    #[test]
+    fn path_touching_exactly_at_the_boundary_counts() {
+        // Ending right on the edge of the circle.
+        let along = sweep_circle(Vec2::new(-10.0, 0.0), Vec2::new(-5.0, 0.0), Vec2::ZERO, 5.0);
+        assert_eq!(along, Some(1.0));
+        // Starting right on it.
+        let along = sweep_circle(Vec2::new(5.0, 0.0), Vec2::new(10.0, 0.0), Vec2::ZERO, 5.0);
+        assert_eq!(along, Some(0.0));
+    }
+
+    #[test]
+    fn path_that_passes_by_misses() {
+        assert_eq!(sweep_circle(Vec2::new(-10.0, 6.0), Vec2::new(10.0, 6.0), Vec2::ZERO, 5.0), None);
+        // Heading straight for it, but stopping short.
+        assert_eq!(sweep_circle(Vec2::new(-20.0, 0.0), Vec2::new(-10.0, 0.0), Vec2::ZERO, 5.0), None);
+    }
+
+    #[test]
+    fn zero_length_path_only_touches_from_inside() {
+        let outside = Vec2::new(10.0, 0.0);
+        assert_eq!(sweep_circle(outside, outside, Vec2::ZERO, 5.0), None);
+        let inside = Vec2::new(3.0, 0.0);
+        assert_eq!(sweep_circle(inside, inside, Vec2::ZERO, 5.0), Some(0.0));
+    }
+
+    #[test]
+    fn candidates_along_include_everything_the_path_touches() {
+        let mut grid = SpatialGrid::default();
+        let near = Entity::from_raw(0);
+        let far = Entity::from_raw(1);
+        grid.insert(near, Vec2::new(500.0, 20.0), 40.0);
+        grid.insert(far, Vec2::new(500.0, 900.0), 40.0);
+
+        let candidates = grid.candidates_along(Vec2::ZERO, Vec2::new(1000.0, 0.0), 15.0);
+        assert!(candidates.iter().any(|candidate| candidate.entity == near));
+        assert!(!candidates.iter().any(|candidate| candidate.entity == far));
+    }
+}
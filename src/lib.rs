@@ -0,0 +1,4 @@
+//! The parts of the game that stand on their own,
+//! so the benchmarks under `benches/` can use them as well as the game.
+
+pub mod grid;
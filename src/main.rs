@@ -3,106 +3,275 @@ use bevy::{
     color::palettes::css::*,
 };
 use bevy::color::palettes::tailwind::*;
-use bevy::input::common_conditions::input_pressed;
-use bevy::render::view::NoFrustumCulling;
+use bevy::input::InputSystem;
+use bevy::transform::TransformSystem;
 use bevy_prototype_lyon::prelude::*;
 
-use rand::random;
-
-// Look... there isn't a good way to draw a circle
-// So instead I'm just making a many-sided regular polygon
-macro_rules! circle {
-    ($radius:expr, $pos:expr) => {{
-        let rad_f32 = ::std::convert::Into::<f32>::into($radius);
-        let circle = ::bevy_prototype_lyon::shapes::RegularPolygon {
-            sides: 6 + (rad_f32 * 0.2).floor() as usize * 3usize,
-            feature: ::bevy_prototype_lyon::shapes::RegularPolygonFeature::Radius(rad_f32),
-            center: ::std::convert::Into::<Vec2>::into($pos),
-        };
-        ::bevy_prototype_lyon::entity::ShapeBundle {
-            path: ::bevy_prototype_lyon::geometry::GeometryBuilder::build_as(&circle),
-            ..default()
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+
+mod balance;
+mod replay;
+mod save;
+mod simulation;
+mod spatial;
+
+fn main() {
+    let mut args = std::env::args().skip(1).collect::<Vec<_>>();
+
+    let (balance, balance_path) = match balance::balance_from_args(&mut args) {
+        Ok(loaded) => loaded,
+        Err(err) => {
+            eprintln!("{err}");
+            std::process::exit(2);
         }
-    }}
-}
+    };
 
-macro_rules! to_vec2 {
-    ($pos:expr) => {
-        ::std::convert::Into::<Vec2>::into($pos)
+    if args.first().is_some_and(|arg| arg == "simulate") {
+        std::process::exit(simulation::run_from_args(&args[1..], &balance));
     }
-}
 
-fn main() {
-    App::new()
+    let (chosen_seed, playback) = if args.first().is_some_and(|arg| arg == "replay") {
+        match replay::replay_from_args(&args[1..], &balance) {
+            Ok(replay) => (ChosenSeed(Some(replay.seed)), Some(replay::ReplayPlayback::new(replay))),
+            Err(err) => {
+                eprintln!("{err}\n\nusage: circles-infinity replay <file> [--verify]");
+                std::process::exit(2);
+            }
+        }
+    } else {
+        match parse_seed_args(&args) {
+            Ok(chosen_seed) => (chosen_seed, None),
+            Err(err) => {
+                eprintln!("{err}\n\nusage: circles-infinity [--seed <u64> | --daily]");
+                std::process::exit(2);
+            }
+        }
+    };
+
+    let save_data = save::load_save_data();
+
+    let mut app = App::new();
+    app
         .insert_resource(Msaa::Sample4)
         .add_plugins((
             DefaultPlugins,
             ShapePlugin,
+            GameplayPlugin,
         ))
-        .add_systems(Startup, (
-            setup,
-            start_on_menu,
-        ).chain())
+        .insert_resource(save_data)
+        .insert_resource(chosen_seed)
+        .insert_resource(balance)
+        .insert_resource(balance::BalanceSource::new(balance_path))
+        .add_systems(Startup, setup)
+        .add_systems(OnEnter(GameScreen::MainMenu), spawn_main_menu)
+        .add_systems(OnEnter(GameScreen::Guide), spawn_guide)
+        .add_systems(OnEnter(GameScreen::Running), spawn_hud.after(start_run))
+        .add_systems(OnEnter(GameScreen::LoseScreen), spawn_lose_screen)
+        .add_systems(OnEnter(RunState::Paused), (pause_time, spawn_pause_overlay))
+        .add_systems(OnExit(RunState::Paused), unpause_time)
+        // Reading input before the fixed timestep runs means
+        // a key press is acted on in the same frame it happens.
+        .add_systems(PreUpdate, handle_running_input
+            .after(InputSystem)
+            .run_if(in_state(RunState::Playing))
+        )
         .add_systems(Update, (
-            guide_to_menu.run_if(resource_exists::<Transition<Guide, MainMenu>>),
-            menu_to_guide.run_if(resource_exists::<Transition<MainMenu, Guide>>),
-            menu_to_running.run_if(resource_exists::<Transition<MainMenu, Running>>),
-            running_to_lose_screen
-                .run_if(resource_exists::<Transition<Running, LoseScreen>>)
-                .before(check_and_resolve_player_death),
-            lose_screen_to_running.run_if(resource_exists::<Transition<LoseScreen, Running>>),
-            lose_screen_to_menu.run_if(resource_exists::<Transition<LoseScreen, MainMenu>>),
-
-            handle_menu_input.run_if(resource_exists::<MainMenu>),
-            handle_paused_input.run_if(resource_exists::<Paused>),
-            handle_running_input.run_if(resource_exists::<Running>),
-            handle_guide_input.run_if(resource_exists::<Guide>),
-            handle_lose_screen_input.run_if(resource_exists::<LoseScreen>),
-
-            update_player_color.run_if(resource_exists::<Running>),
-            update_player_health_text.run_if(resource_exists::<Running>),
-
-            update_player
-                .before(player_ranged_attack)
-                .before(enemy_update_and_attack)
-                .run_if(resource_exists::<Running>),
-
-            (
-                update_power_ups,
-                create_power_ups,
-                collect_power_ups
-            ).run_if(resource_exists::<Running>).chain(),
-            (
-                player_ranged_attack
-                    .run_if(input_pressed(MouseButton::Left)),
-                resolve_enemy_projectiles,
-                check_and_resolve_player_death,
-            ).run_if(resource_exists::<Running>).chain(),
-
-            resolve_player_projectiles.run_if(resource_exists::<Running>),
+            balance::hot_reload_balance,
+            handle_menu_input.run_if(in_state(GameScreen::MainMenu)),
+            handle_paused_input.run_if(in_state(RunState::Paused)),
+            handle_guide_input.run_if(in_state(GameScreen::Guide)),
+            handle_lose_screen_input.run_if(in_state(GameScreen::LoseScreen)),
 
             (
-                move_projectiles,
-                do_splashes,
-                handle_residue,
-                remove_dead_enemies,
-                enemy_update_and_attack,
-                spawn_wave_if_no_enemies,
-            ).run_if(resource_exists::<Running>).chain()
-
+                follow_player_with_camera,
+                update_player_color,
+                update_player_health_text,
+                update_close_attack_text,
+                update_weapon_text,
+                update_upgrade_screen,
+                update_draft_panel.run_if(resource_changed::<Draft>),
+                update_boss_health_bar,
+                update_score_text.run_if(resource_changed::<RunStats>),
+                update_intermission_text.run_if(resource_changed::<Intermission>),
+                update_wave_counter_text.run_if(resource_changed::<WaveCounter>),
+                update_player_upgrade_counter_text.run_if(resource_changed::<PlayerUpgradeCounter>),
+            ).run_if(in_state(RunState::Playing)),
         ))
-        .run();
+        .add_systems(PostUpdate, pulse_power_ups.before(TransformSystem::TransformPropagate));
+
+    // Watching a replay skips the main menu and feeds the replay's input
+    // to the game in place of the keyboard and mouse.
+    if let Some(playback) = playback {
+        app
+            .insert_resource(playback)
+            .add_systems(OnEnter(GameScreen::Running), replay::restart_playback)
+            .add_systems(FixedUpdate, replay::play_back_input
+                .before(GameplaySystems)
+                .run_if(in_state(RunState::Playing))
+            );
+        app.world_mut().resource_mut::<NextState<GameScreen>>().set(GameScreen::Running);
+    }
+
+    app.run();
+}
+
+/// Everything that makes up a run, without any rendering, windowing or device input.
+///
+/// Gameplay advances in `FixedUpdate` and reads what the player is doing
+/// from the `PlayerInput` resource, so the same systems can be driven by the
+/// keyboard and mouse or by a script in a headless simulation.
+/// The systems run one after another in a fixed order and all randomness comes
+/// from `GameRng`, so the same seed and inputs always give the same run.
+struct GameplayPlugin;
+
+#[derive(SystemSet)]
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+struct GameplaySystems;
+
+impl Plugin for GameplayPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            // These have to exist before the first `OnEnter(GameScreen::MainMenu)`,
+            // which runs ahead of the `Startup` schedule.
+            .init_resource::<WaveCounter>()
+            .init_resource::<PlayerUpgradeCounter>()
+            .init_resource::<Draft>()
+            .init_resource::<RunStats>()
+            .init_resource::<PendingGroups>()
+            .init_resource::<Intermission>()
+            .init_resource::<PowerUpSpawnTimer>()
+            .init_resource::<PlayerInput>()
+            .init_resource::<GameRng>()
+            .init_resource::<ChosenSeed>()
+            .init_resource::<RunSeed>()
+            .init_resource::<balance::Balance>()
+            .init_resource::<spatial::SpatialIndex>()
+            .init_resource::<replay::ReplayRecorder>()
+            .add_event::<DamageEvent>()
+            .add_event::<ProjectileHit>()
+            .add_event::<EnemyKilled>()
+            .init_state::<GameScreen>()
+            .add_sub_state::<RunState>()
+            .enable_state_scoped_entities::<GameScreen>()
+            .enable_state_scoped_entities::<RunState>()
+            .add_systems(OnEnter(GameScreen::Running), (start_run, replay::start_recording).chain())
+            .configure_sets(FixedUpdate, GameplaySystems
+                .run_if(in_state(RunState::Playing))
+                .run_if(player_is_alive)
+            )
+            // A tuple can only hold 20 systems, so the chain is split into groups that run one after another.
+            .add_systems(FixedUpdate, (
+                (
+                    replay::record_player_input,
+                    apply_player_input,
+                    update_player,
+                    regenerate_health,
+                    tick_run_time,
+                    update_power_ups,
+                    create_power_ups,
+                    expire_projectiles,
+                    steer_homing_projectiles,
+                    spatial::rebuild_spatial_index,
+                    collect_power_ups,
+                    steer_seeking_projectiles,
+                    return_boomerangs,
+                    orbit_blades,
+                ).chain(),
+                (
+                    player_ranged_attack,
+                    player_close_attack,
+                    resolve_enemy_projectiles,
+                    detonate_mines,
+                    resolve_player_projectiles,
+                    splash_on_hit,
+                    split_on_hit,
+                    chain_on_hit,
+                    ricochet_on_hit,
+                    despawn_spent_projectiles,
+                    move_projectiles,
+                    pulse_lingering_pools,
+                    do_splashes,
+                ).chain(),
+                (
+                    apply_damage,
+                    spawn_damage_numbers,
+                    handle_residue,
+                    float_damage_numbers,
+                    fade_hit_flashes,
+                    remove_dead_enemies,
+                    score_kills,
+                    heal_on_kill,
+                    spawn_death_puffs,
+                ).chain(),
+                (
+                    boss_attacks,
+                    enemy_update_and_attack,
+                    heal_nearby_enemies,
+                    spawn_pending_groups,
+                    resolve_spawn_markers,
+                    spawn_wave_if_no_enemies,
+                    deal_upgrade_cards,
+                    check_and_resolve_player_death,
+                ).chain(),
+            ).chain().in_set(GameplaySystems));
+    }
+}
+
+/// Once the player dies, nothing else should happen before the lose screen shows up,
+/// even if more fixed ticks are due this frame.
+fn player_is_alive(player: Query<&Health, With<Player>>) -> bool {
+    player.get_single().is_ok_and(|health| health.current_health() > 0)
 }
 
 fn setup(mut commands: Commands) {
     commands.spawn(Camera2dBundle::default());
-    commands.insert_resource(WaveCounter::default());
-    commands.insert_resource(PlayerUpgradeCounter::default());
-    commands.insert_resource(HighScore::default());
-    commands.insert_resource(PowerUpSpawnTimer::default())
 }
 
-const ENEMY_COLOR: Srgba = ORANGE_800;
+/// `--seed <u64>` plays every run with that seed,
+/// `--daily` plays every run with a seed that's the same for everyone on a given day.
+fn parse_seed_args(args: &[String]) -> Result<ChosenSeed, String> {
+    let mut chosen_seed = ChosenSeed(None);
+    let mut args = args.iter();
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--seed" => {
+                let value = args.next().ok_or("`--seed` needs a value")?;
+                let seed = value.parse().map_err(|err| format!("invalid seed `{value}`: {err}"))?;
+                chosen_seed.0 = Some(seed);
+            },
+            "--daily" => chosen_seed.0 = Some(daily_seed()),
+            _ => return Err(format!("unknown argument `{arg}`")),
+        }
+    }
+
+    Ok(chosen_seed)
+}
+
+/// Today's date (in UTC) written as a number, like 20240731,
+/// so it's easy to tell which day a shared daily seed belongs to.
+fn daily_seed() -> u64 {
+    let seconds = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |since_epoch| since_epoch.as_secs());
+
+    // Converts days since 1970-01-01 into a civil date,
+    // see http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+    let days = (seconds / 86_400) as i64 + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days.rem_euclid(146_097);
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month = if shifted_month < 10 { shifted_month + 3 } else { shifted_month - 9 };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    (year * 10_000 + month * 100 + day) as u64
+}
+
+/// The size of a chaser, the most common enemy.
 const ENEMY_RADIUS: f32 = 40.0;
 
 const PLAYER_COLOR_MAX_HP: Srgba = GREEN_800;
@@ -111,33 +280,75 @@ const PLAYER_RADIUS: f32 = 50.0;
 
 const BASE_PROJECTILE_RADIUS: f32 = 15.0;
 
-fn menu_to_running(
+const POWER_UP_RADIUS: f32 = 15.0;
+/// How much bigger (and smaller) than `POWER_UP_RADIUS` a power-up gets as it pulses.
+const POWER_UP_PULSE: f32 = 3.0;
+const DAMAGE_NUMBER_RISE_SPEED: f32 = 60.0;
+/// How long it takes knockback to slow down to half its speed.
+const KNOCKBACK_HALF_LIFE: f32 = 0.1;
+/// Seconds between each time a lingering pool splashes.
+const POOL_PULSE_INTERVAL: f32 = 0.5;
+
+/// A circle centered on its `Transform`, so it's only tessellated once
+/// and moving it is just a matter of moving the `Transform`.
+fn circle_shape(radius: f32, pos: Vec2) -> ShapeBundle {
+    let circle = shapes::Circle { radius, center: Vec2::ZERO };
+    ShapeBundle {
+        path: GeometryBuilder::build_as(&circle),
+        spatial: SpatialBundle::from_transform(Transform::from_translation(pos.extend(0.0))),
+        ..default()
+    }
+}
+
+/// Spawns the player and resets everything for a fresh run.
+/// Everything spawned here is scoped to `GameScreen::Running`,
+/// so it gets cleaned up automatically once the run ends.
+fn start_run(
     mut player_upgrade_counter: ResMut<PlayerUpgradeCounter>,
+    mut draft: ResMut<Draft>,
     mut wave_counter: ResMut<WaveCounter>,
+    mut run_stats: ResMut<RunStats>,
+    mut pending_groups: ResMut<PendingGroups>,
+    mut intermission: ResMut<Intermission>,
+    mut power_up_spawn_timer: ResMut<PowerUpSpawnTimer>,
+    mut player_input: ResMut<PlayerInput>,
+    mut rng: ResMut<GameRng>,
+    mut run_seed: ResMut<RunSeed>,
+    chosen_seed: Res<ChosenSeed>,
+    balance: Res<balance::Balance>,
     mut commands: Commands,
-    main_menu_items: Query<Entity, With<MainMenuItem>>
 ) {
-    for id in main_menu_items.iter() {
-        commands.entity(id).despawn()
-    }
+    // Picking the seed is the one place randomness doesn't come from `GameRng`,
+    // since this is where `GameRng` gets its seed from.
+    run_seed.0 = chosen_seed.0.unwrap_or_else(rand::random);
+    *rng = GameRng::seeded(run_seed.0);
+
+    *run_stats = RunStats::default();
+    pending_groups.0.clear();
+    intermission.0 = None;
+    *power_up_spawn_timer = PowerUpSpawnTimer::new(balance.power_ups.spawn_interval);
+    *player_input = PlayerInput::default();
+    wave_counter.0 = 0;
+    player_upgrade_counter.reset();
+    *draft = Draft::new(&balance.draft);
 
-    let stats = PlayerStats::default();
+    let stats = balance.player.stats;
     commands.spawn((
-        RunningObject,
-        NoFrustumCulling, // prevent weird invisibility
+        StateScoped(GameScreen::Running),
         Player,
-        Health::new(100),
+        Health::new(balance.player.health),
         stats,
+        Passives::default(),
         PlayerState::from_player_stats(stats),
-        Position::new(0.0, 0.0),
-        circle!(PLAYER_RADIUS, Position::new(0.0, 0.0)),
+        circle_shape(PLAYER_RADIUS, Vec2::ZERO),
         Fill::color(PLAYER_COLOR_MAX_HP),
         Stroke::new(BLACK, 5.0),
     ));
+}
 
-    wave_counter.0 = 0;
+fn spawn_hud(player_upgrade_counter: Res<PlayerUpgradeCounter>, mut commands: Commands) {
     commands.spawn((
-        RunningObject,
+        StateScoped(GameScreen::Running),
         WaveCounterText,
         TextBundle::from_section("Wave 0", TextStyle::default())
             .with_style(Style {
@@ -148,9 +359,8 @@ fn menu_to_running(
             }),
     ));
 
-    player_upgrade_counter.reset();
     commands.spawn((
-        RunningObject,
+        StateScoped(GameScreen::Running),
         PlayerUpgradeCounterText,
         TextBundle::from_section(player_upgrade_counter.display_text(), TextStyle::default())
             .with_style(Style {
@@ -162,115 +372,127 @@ fn menu_to_running(
     ));
 
     commands.spawn((
-        RunningObject,
-        PlayerHealthText,
-        TextBundle::from_section("Health: 100 / 100", TextStyle::default())
+        StateScoped(GameScreen::Running),
+        ScoreText,
+        TextBundle::from_section("Score: 0", TextStyle::default())
             .with_style(Style {
                 position_type: PositionType::Absolute,
-                top: Val::Percent(5.0),
+                top: Val::Percent(8.0),
                 left: Val::Percent(2.0),
                 ..default()
             })
     ));
 
-    commands.remove_resource::<Transition<MainMenu, Running>>();
-    commands.insert_resource(Running);
-}
-
-fn menu_to_guide(mut commands: Commands, entities: Query<Entity, With<MainMenuItem>>) {
-    const GUIDE_TEXT: &'static str = "Welcome to the guide, where you learn how the game works.\n\n\
-    You are the green circle (although the color will become more red as you lose health). Enemies, \
-    which are orange circles will spawn around you in waves. Your goal is to survive as many waves \
-    as possible. To get to the next wave, you will need to kill every enemy. By left clicking and \
-    holding, you will create projectiles which damage enemies. You can press Q or E to switch \
-    which projectile you are using.\n\n\
-    You can also upgrade your player by pressing one of the number keys (you get an extra upgrade \
-    after beating each round). The types of upgrades are Health, Attack, and Speed.\n\n\
-    Brightly colored, pulsating powerups will periodically spawn. Collecting these will give you \
-    temporary boosts!\n\n\
-    Press Escape to return to the home screen.";
-
-    for id in entities.iter() {
-        commands.entity(id).despawn();
-    }
-
     commands.spawn((
-        GuideItem,
-        TextBundle::from_section(GUIDE_TEXT, TextStyle::default())
+        StateScoped(GameScreen::Running),
+        CloseAttackText,
+        TextBundle::from_section("", TextStyle::default())
             .with_style(Style {
                 position_type: PositionType::Absolute,
-                top: Val::Percent(25.0),
-                left: Val::Percent(10.0),
-                right: Val::Percent(10.0),
+                top: Val::Percent(11.0),
+                left: Val::Percent(2.0),
                 ..default()
             })
     ));
 
-    commands.remove_resource::<Transition<MainMenu, Guide>>();
-    commands.insert_resource(Guide);
-}
-
-fn guide_to_menu(mut commands: Commands, entities: Query<Entity, With<GuideItem>>, high_score: Res<HighScore>) {
-    for id in entities.iter() {
-        commands.entity(id).despawn();
-    }
-
-    commands.remove_resource::<Transition<Guide, MainMenu>>();
-    start_on_menu(commands, high_score);
-}
-
-fn lose_screen_to_running(
-    mut player_upgrade_counter: ResMut<PlayerUpgradeCounter>,
-    mut wave_counter: ResMut<WaveCounter>,
-    mut commands: Commands,
-    entities: Query<Entity, With<LoseScreenItem>>,
-) {
-    for entity in entities.iter() {
-        commands.entity(entity).despawn();
-    }
+    commands.spawn((
+        StateScoped(GameScreen::Running),
+        WeaponText,
+        TextBundle::from_section("", TextStyle::default())
+            .with_style(Style {
+                position_type: PositionType::Absolute,
+                top: Val::Percent(14.0),
+                left: Val::Percent(2.0),
+                ..default()
+            })
+    ));
 
-    let stats = PlayerStats::default();
     commands.spawn((
-        RunningObject,
-        NoFrustumCulling, // prevent weird invisibility
-        Player,
-        Health::new(100),
-        stats,
-        PlayerState::from_player_stats(stats),
-        Position::new(0.0, 0.0),
-        circle!(PLAYER_RADIUS, Position::new(0.0, 0.0)),
-        Fill::color(PLAYER_COLOR_MAX_HP),
-        Stroke::new(BLACK, 5.0),
+        StateScoped(GameScreen::Running),
+        IntermissionText,
+        TextBundle::from_section("", TextStyle::default())
+            .with_style(Style {
+                position_type: PositionType::Absolute,
+                top: Val::Percent(6.0),
+                left: Val::Percent(25.0),
+                right: Val::Percent(25.0),
+                justify_content: JustifyContent::Center,
+                ..default()
+            })
+            .with_text_justify(JustifyText::Center),
     ));
 
-    wave_counter.0 = 0;
+    // Only shown between waves.
     commands.spawn((
-        RunningObject,
-        WaveCounterText,
-        TextBundle::from_section("Wave 0", TextStyle::default())
+        StateScoped(GameScreen::Running),
+        UpgradeScreen,
+        TextBundle::from_section("", TextStyle::default())
+            .with_background_color(Color::BLACK.with_alpha(0.7))
             .with_style(Style {
                 position_type: PositionType::Absolute,
-                top: Val::Percent(2.0),
-                left: Val::Percent(2.0),
+                top: Val::Percent(30.0),
+                right: Val::Percent(2.0),
+                width: Val::Percent(30.0),
+                padding: UiRect::all(Val::Px(10.0)),
                 ..default()
             }),
     ));
 
-    player_upgrade_counter.reset();
+    // Only shown while there are upgrade cards on offer, filled in by `update_draft_panel`.
     commands.spawn((
-        RunningObject,
-        PlayerUpgradeCounterText,
-        TextBundle::from_section(player_upgrade_counter.display_text(), TextStyle::default())
-            .with_style(Style {
+        StateScoped(GameScreen::Running),
+        DraftPanel,
+        NodeBundle {
+            style: Style {
                 position_type: PositionType::Absolute,
                 bottom: Val::Percent(2.0),
-                left: Val::Percent(2.0),
+                left: Val::Percent(25.0),
+                right: Val::Percent(25.0),
+                flex_wrap: FlexWrap::Wrap,
+                justify_content: JustifyContent::Center,
+                column_gap: Val::Px(10.0),
+                row_gap: Val::Px(6.0),
                 ..default()
-            }),
+            },
+            visibility: Visibility::Hidden,
+            ..default()
+        },
     ));
 
+    // Only shown while there's a boss to fight.
+    commands.spawn((
+        StateScoped(GameScreen::Running),
+        BossHealthBar,
+        NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                top: Val::Percent(2.0),
+                left: Val::Percent(25.0),
+                width: Val::Percent(50.0),
+                height: Val::Px(16.0),
+                ..default()
+            },
+            background_color: BackgroundColor(GRAY_800.into()),
+            visibility: Visibility::Hidden,
+            ..default()
+        },
+    )).with_children(|bar| {
+        bar.spawn((
+            BossHealthBarFill,
+            NodeBundle {
+                style: Style {
+                    width: Val::Percent(100.0),
+                    height: Val::Percent(100.0),
+                    ..default()
+                },
+                background_color: BackgroundColor(EnemyKind::Boss.color().into()),
+                ..default()
+            },
+        ));
+    });
+
     commands.spawn((
-        RunningObject,
+        StateScoped(GameScreen::Running),
         PlayerHealthText,
         TextBundle::from_section("Health: 100 / 100", TextStyle::default())
             .with_style(Style {
@@ -280,39 +502,106 @@ fn lose_screen_to_running(
                 ..default()
             })
     ));
-
-    commands.remove_resource::<Transition<LoseScreen, Running>>();
-    commands.insert_resource(Running);
 }
 
-fn lose_screen_to_menu(mut commands: Commands, entities: Query<Entity, With<LoseScreenItem>>, high_score: Res<HighScore>) {
-    for entity in entities.iter() {
-        commands.entity(entity).despawn();
-    }
+fn spawn_guide(mut commands: Commands) {
+    const GUIDE_TEXT: &'static str = "Welcome to the guide, where you learn how the game works.\n\n\
+    You are the green circle (although the color will become more red as you lose health). Enemies \
+    will spawn around you in waves: orange ones chase you, red ones keep their distance and shoot, \
+    yellow ones dash at you, big grey ones take a beating, green ones split apart when they die, \
+    and blue ones heal the enemies around them. Every so often a boss shows up on its own, and \
+    beating it earns you extra upgrades. Faint circles show where enemies are about to appear.\n\n\
+    Shooters get smarter as the waves go on, aiming ahead of you and firing in fans, and later on \
+    other enemies start shooting too: rings of bullets, slow shots that follow you, and mines that \
+    blow up after a moment, so stay out of the orange circles.\n\n\
+    Your goal is to survive as many waves as possible. To get to the next wave, you will need to \
+    kill every enemy. Between waves you get a short break, and pressing Space ends it early for \
+    some extra score. By left clicking and \
+    holding, you will create projectiles which damage enemies. You start with one weapon and \
+    unlock more as the waves go on, from shotguns to lasers to spinning blades, and you can press \
+    Q or E to switch between them. Right clicking sends out a shockwave that hurts and pushes away \
+    every enemy close to you, but it takes a moment to recharge.\n\n\
+    After beating each round you get an upgrade, and three random upgrade cards are dealt at the \
+    bottom of the screen. Click on one or press 1, 2 or 3 to pick it. Common cards make you \
+    tougher, faster or stronger, rare ones give you new abilities, and legendary ones change every \
+    weapon you have. A few times a run you can press R to deal new cards, or right click a card \
+    (or hold Shift and press its number) to banish it for good. Between waves you can instead \
+    spend an upgrade on levelling up the weapon you're holding: each weapon has its own upgrades, \
+    and picking one branch rules out the others.\n\n\
+    Brightly colored, pulsating powerups will periodically spawn. Collecting these will give you \
+    temporary boosts!\n\n\
+    You can pause a run at any time by pressing Escape.\n\n\
+    Press Escape to return to the home screen.";
 
-    commands.remove_resource::<Transition<LoseScreen, MainMenu>>();
-    start_on_menu(commands, high_score);
+    commands.spawn((
+        StateScoped(GameScreen::Guide),
+        TextBundle::from_section(GUIDE_TEXT, TextStyle::default())
+            .with_style(Style {
+                position_type: PositionType::Absolute,
+                top: Val::Percent(25.0),
+                left: Val::Percent(10.0),
+                right: Val::Percent(10.0),
+                ..default()
+            })
+    ));
 }
 
-fn running_to_lose_screen(
+fn spawn_lose_screen(
     wave_counter: Res<WaveCounter>,
-    mut high_score: ResMut<HighScore>,
+    run_stats: Res<RunStats>,
+    balance: Res<balance::Balance>,
+    run_seed: Res<RunSeed>,
+    recorder: Res<replay::ReplayRecorder>,
+    playback: Option<Res<replay::ReplayPlayback>>,
+    mut save_data: ResMut<save::SaveData>,
     mut commands: Commands,
-    entities: Query<Entity, With<RunningObject>>,
 ) {
-    for entity in entities.iter() {
-        commands.entity(entity).despawn();
-    }
+    let previous_best_wave = save_data.best_wave;
+    let previous_best_score = save_data.best_score;
+    let score = run_stats.score(wave_counter.0, &balance.score);
+
+    // Watching a replay shouldn't count as playing.
+    let (new_best_wave, replay_text) = if playback.is_some() {
+        (false, "That was a replay.".to_string())
+    } else {
+        record_finished_run(&mut save_data, wave_counter.0, &run_stats, score, &recorder, true)
+    };
 
-    if wave_counter.0 <= high_score.0 {
+    let best_score_text = if playback.is_none() && score > previous_best_score {
+        format!("You scored {score} points, a new best! Your previous best score was {previous_best_score}.")
+    } else {
+        format!("You scored {score} points, your best score is {previous_best_score}.")
+    };
+    let weapon_kills = balance.weapons.roster.iter().enumerate()
+        .filter_map(|(index, weapon)| {
+            let kills = run_stats.kills_by_weapon.get(index).copied().unwrap_or(0);
+            (kills > 0).then(|| format!("{kills} {}", weapon.name.to_lowercase()))
+        })
+        .chain([format!("{} shockwave", run_stats.close_attack_kills)])
+        .collect::<Vec<_>>()
+        .join(", ");
+    let score_text = format!(
+        "{best_score_text}\nKills by weapon: {weapon_kills}.\nYou dealt {} damage and took {}.",
+        run_stats.damage_dealt,
+        run_stats.damage_taken,
+    );
+
+    if !new_best_wave {
         commands.spawn((
-            LoseScreenItem,
+            StateScoped(GameScreen::LoseScreen),
             TextBundle::from_section(format!(
-                    "You lost on wave {}, your best wave is {}.\n\n\
+                    "You lost on wave {}, your best wave is {}.\n\
+                    {}\n\n\
+                    This run's seed was {}. Start the game with `--seed {}` to play it again.\n\
+                    {}\n\n\
                     Thanks for playing! Press enter to play again, \
                     or press Escape to return to the main menu.",
                     wave_counter.0,
-                    high_score.0,
+                    previous_best_wave,
+                    score_text,
+                    run_seed.0,
+                    run_seed.0,
+                    replay_text,
                 ), TextStyle::default())
                 .with_style(Style {
                     position_type: PositionType::Absolute,
@@ -327,13 +616,20 @@ fn running_to_lose_screen(
         ));
     } else {
         commands.spawn((
-            LoseScreenItem,
+            StateScoped(GameScreen::LoseScreen),
             TextBundle::from_section(format!(
-                "New high score: {}! Your previous best wave was {}.\n\n\
+                "New best wave: {}! Your previous best wave was {}.\n\
+                {}\n\n\
+                This run's seed was {}. Start the game with `--seed {}` to play it again.\n\
+                {}\n\n\
                 Thanks for playing! Press enter to play again, \
                 or press Escape to return to the main menu.",
                 wave_counter.0,
-                high_score.0,
+                previous_best_wave,
+                score_text,
+                run_seed.0,
+                run_seed.0,
+                replay_text,
             ), TextStyle::default())
                 .with_style(Style {
                     position_type: PositionType::Absolute,
@@ -346,120 +642,368 @@ fn running_to_lose_screen(
                     ..default()
                 }),
         ));
-        high_score.0 = wave_counter.0;
     }
-
-    commands.remove_resource::<Transition<Running, LoseScreen>>();
-    commands.insert_resource(LoseScreen);
 }
 
-fn handle_menu_input(mut commands: Commands, keyboard: Res<ButtonInput<KeyCode>>) {
+fn handle_menu_input(mut next_screen: ResMut<NextState<GameScreen>>, keyboard: Res<ButtonInput<KeyCode>>) {
     if keyboard.pressed(KeyCode::Enter) {
-        commands.remove_resource::<MainMenu>();
-        commands.insert_resource(Transition::new(MainMenu, Running));
+        next_screen.set(GameScreen::Running);
     }
     if keyboard.pressed(KeyCode::KeyG) {
-        commands.remove_resource::<MainMenu>();
-        commands.insert_resource(Transition::new(MainMenu, Guide));
+        next_screen.set(GameScreen::Guide);
     }
 }
 
+/// Turns the keyboard and mouse into a `PlayerInput` for the next gameplay tick.
 fn handle_running_input(
-    mut player_upgrade_counter: ResMut<PlayerUpgradeCounter>,
-    mut player_upgrade_counter_text: Query<&mut Text, With<PlayerUpgradeCounterText>>,
-    mut player: Query<(&mut PlayerStats, &mut Health, &mut PlayerState), With<Player>>,
+    mut next_run_state: ResMut<NextState<RunState>>,
+    mut player_input: ResMut<PlayerInput>,
+    window: Query<&Window>,
+    camera: Query<(&Camera, &GlobalTransform)>,
+    cards: Query<(&Interaction, &DraftCardButton)>,
     keyboard: Res<ButtonInput<KeyCode>>,
+    mouse: Res<ButtonInput<MouseButton>>,
+    playback: Option<Res<replay::ReplayPlayback>>,
 ) {
-    // didn't feel like implementing pause menu
-    /*
     if keyboard.just_pressed(KeyCode::Escape) {
-        commands.remove_resource::<Running>();
-        commands.insert_resource(Transition::new(Running, Paused));
+        next_run_state.set(RunState::Paused);
+        return;
+    }
+
+    // A replay is being watched, so its input is used instead.
+    if playback.is_some() {
+        return;
+    }
+
+    let w = keyboard.pressed(KeyCode::KeyW) || keyboard.pressed(KeyCode::ArrowUp);
+    let a = keyboard.pressed(KeyCode::KeyA) || keyboard.pressed(KeyCode::ArrowLeft);
+    let s = keyboard.pressed(KeyCode::KeyS) || keyboard.pressed(KeyCode::ArrowDown);
+    let d = keyboard.pressed(KeyCode::KeyD) || keyboard.pressed(KeyCode::ArrowRight);
+
+    let mut vertical_movement = if w && !s { 1f32 } else if s && !w { -1f32 } else { 0f32 };
+    let mut horizontal_movement = if d && !a { 1f32 } else if a && !d { -1f32 } else { 0f32 };
+    if vertical_movement != 0.0 && horizontal_movement != 0.0 {
+        let sqrt_2 = 2f32.sqrt();
+        vertical_movement /= sqrt_2;
+        horizontal_movement /= sqrt_2;
     }
-    */
+    player_input.movement = Vec2::new(horizontal_movement, vertical_movement);
 
-    let (mut stats, mut health, mut state) = player.single_mut();
+    let (camera, transform) = camera.single();
+    player_input.aim = window.single()
+        .cursor_position()
+        .and_then(|mouse| camera.viewport_to_world_2d(transform, mouse));
+
+    // Clicking on a card picks it, and right clicking banishes it, instead of attacking.
+    let hovered_card = cards.iter()
+        .find(|(interaction, _)| **interaction != Interaction::None)
+        .map(|(_, card)| card.0);
+    if let Some(slot) = hovered_card {
+        player_input.firing = false;
+        if mouse.just_pressed(MouseButton::Left) {
+            player_input.upgrade = Some(PlayerUpgrade::Card(slot));
+        } else if mouse.just_pressed(MouseButton::Right) {
+            player_input.upgrade = Some(PlayerUpgrade::Banish(slot));
+        }
+    } else {
+        player_input.firing = mouse.pressed(MouseButton::Left);
+        if mouse.just_pressed(MouseButton::Right) {
+            player_input.close_attack = true;
+        }
+    }
 
+    // One-shot actions stay set until a gameplay tick uses them up,
+    // since a frame can go by without any fixed ticks running.
     if keyboard.just_pressed(KeyCode::KeyQ) {
-        state.current_weapon.cycle_left();
-        state.ranged_attack_timer = Timer::from_seconds(
-            stats.ranged_attack_cooldown * state.current_weapon.cooldown_multiplier(),
-            TimerMode::Repeating,
-        );
+        player_input.cycle_weapon_left = true;
     } else if keyboard.just_pressed(KeyCode::KeyE) {
-        state.current_weapon.cycle_right();
-        state.ranged_attack_timer = Timer::from_seconds(
-            stats.ranged_attack_cooldown * state.current_weapon.cooldown_multiplier(),
-            TimerMode::Repeating,
-        );
+        player_input.cycle_weapon_right = true;
     }
 
-    if player_upgrade_counter.unused_upgrades > 0 {
-        let upgrade: Option<PlayerUpgrade>;
+    if keyboard.just_pressed(KeyCode::Space) {
+        player_input.call_wave = true;
+    }
 
-        if keyboard.just_pressed(KeyCode::Digit1) {
-            upgrade = Some(PlayerUpgrade::HealthUpgrade);
-        } else if keyboard.just_pressed(KeyCode::Digit2) {
-            upgrade = Some(PlayerUpgrade::AttackUpgrade);
-        } else if keyboard.just_pressed(KeyCode::Digit3) {
-            upgrade = Some(PlayerUpgrade::SpeedUpgrade);
-        } else {
-            upgrade = None;
+    // Holding shift banishes the card instead of picking it.
+    let card_keys = [KeyCode::Digit1, KeyCode::Digit2, KeyCode::Digit3];
+    if let Some(slot) = card_keys.iter().position(|key| keyboard.just_pressed(*key)) {
+        let banish = keyboard.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]);
+        player_input.upgrade = Some(if banish { PlayerUpgrade::Banish(slot) } else { PlayerUpgrade::Card(slot) });
+    } else if keyboard.just_pressed(KeyCode::KeyR) {
+        player_input.upgrade = Some(PlayerUpgrade::Reroll);
+    }
+
+    let weapon_keys = [
+        KeyCode::Digit4,
+        KeyCode::Digit5,
+        KeyCode::Digit6,
+        KeyCode::Digit7,
+        KeyCode::Digit8,
+        KeyCode::Digit9,
+    ];
+    if let Some(choice) = weapon_keys.iter().position(|key| keyboard.just_pressed(*key)) {
+        player_input.upgrade = Some(PlayerUpgrade::WeaponUpgrade(choice));
+    }
+}
+
+fn apply_player_input(
+    mut player_input: ResMut<PlayerInput>,
+    mut player_upgrade_counter: ResMut<PlayerUpgradeCounter>,
+    mut draft: ResMut<Draft>,
+    mut intermission: ResMut<Intermission>,
+    mut run_stats: ResMut<RunStats>,
+    wave_counter: Res<WaveCounter>,
+    balance: Res<balance::Balance>,
+    mut player: Query<(&mut PlayerStats, &mut Health, &mut Passives, &mut PlayerState), With<Player>>,
+) {
+    let (mut stats, mut health, mut passives, mut state) = player.single_mut();
+
+    let cycle_left = std::mem::take(&mut player_input.cycle_weapon_left);
+    let cycle_right = std::mem::take(&mut player_input.cycle_weapon_right);
+    if cycle_left || cycle_right {
+        state.current_weapon = balance.weapons.cycle(state.current_weapon, wave_counter.0, cycle_left);
+        state.reset_ranged_attack_timer(&stats, &balance.weapons);
+    }
+
+    // Calling the next wave early is worth more the more of the wait it skips.
+    if std::mem::take(&mut player_input.call_wave) {
+        if let Some(time_left) = intermission.0 {
+            run_stats.bonus_score += (time_left * balance.waves.early_call_score_per_second) as u64;
+            intermission.0 = Some(0.0);
         }
+    }
 
-        if let Some(upgrade) = upgrade {
+    // New cards are dealt by `deal_upgrade_cards`, later in the same tick.
+    match player_input.upgrade.take() {
+        Some(upgrade @ PlayerUpgrade::Card(slot)) => {
+            let Some(&card) = draft.offer.get(slot) else { return };
+            if player_upgrade_counter.unused_upgrades == 0 {
+                return;
+            }
             player_upgrade_counter.unused_upgrades -= 1;
             player_upgrade_counter.add_upgrade(upgrade);
+            draft.record_pick(card);
+            draft.offer.clear();
 
-            *player_upgrade_counter_text.single_mut() =
-                Text::from_section(player_upgrade_counter.display_text(), TextStyle::default());
+            let effect = &balance.draft.cards[card].effect;
+            apply_upgrade_card(&mut stats, &mut health, &mut passives, &mut state, effect, &balance.upgrades);
+            state.reset_ranged_attack_timer(&stats, &balance.weapons);
+        },
+        Some(PlayerUpgrade::Reroll) => {
+            if draft.rerolls_left > 0 && !draft.offer.is_empty() {
+                draft.rerolls_left -= 1;
+                draft.offer.clear();
+            }
+        },
+        Some(PlayerUpgrade::Banish(slot)) => {
+            if draft.banishes_left > 0 && slot < draft.offer.len() {
+                draft.banishes_left -= 1;
+                let card = draft.offer.remove(slot);
+                draft.banished.push(card);
+            }
+        },
+        // Weapons can only be levelled up between waves, with the upgrade screen open.
+        Some(upgrade @ PlayerUpgrade::WeaponUpgrade(choice)) => {
+            let weapon = state.current_weapon;
+            let next = balance.weapons.definition(weapon).next_upgrades(state.taken_upgrades(weapon));
+            let can_upgrade = intermission.0.is_some() && player_upgrade_counter.unused_upgrades > 0;
+            if let Some(&index) = next.get(choice).filter(|_| can_upgrade) {
+                player_upgrade_counter.unused_upgrades -= 1;
+                player_upgrade_counter.add_upgrade(upgrade);
+                state.take_upgrade(weapon, index);
+                state.reset_ranged_attack_timer(&stats, &balance.weapons);
+            }
+        },
+        None => {},
+    }
+}
 
-            apply_player_upgrade(stats.into_inner(), health.into_inner(), upgrade);
+/// Deals a fresh hand of cards whenever there's an upgrade to spend and the last hand was used up,
+/// and tops it back up after a banish. All the draws come from `GameRng`, so they follow the run's seed.
+fn deal_upgrade_cards(
+    mut draft: ResMut<Draft>,
+    mut rng: ResMut<GameRng>,
+    player_upgrade_counter: Res<PlayerUpgradeCounter>,
+    balance: Res<balance::Balance>,
+) {
+    if player_upgrade_counter.unused_upgrades == 0 {
+        if !draft.offer.is_empty() {
+            draft.offer.clear();
         }
+        return;
+    }
+
+    let cards = &balance.draft.cards;
+    while draft.offer.len() < balance.draft.cards_per_draft {
+        let allowed = |index: usize| {
+            !draft.offer.contains(&index)
+                && !draft.banished.contains(&index)
+                && !cards[index].max_picks.is_some_and(|max_picks| draft.picks_of(index) >= max_picks)
+        };
+        let Some(card) = balance.draft.draw(allowed, &mut rng.0) else { break };
+        draft.offer.push(card);
     }
 }
 
-fn handle_paused_input(mut commands: Commands, keyboard: Res<ButtonInput<KeyCode>>) {
-    if keyboard.just_pressed(KeyCode::Space) {
-        commands.remove_resource::<Paused>();
-        commands.insert_resource(Transition::new(Paused, Running));
+fn handle_paused_input(
+    mut next_screen: ResMut<NextState<GameScreen>>,
+    mut next_run_state: ResMut<NextState<RunState>>,
+    mut save_data: ResMut<save::SaveData>,
+    keyboard: Res<ButtonInput<KeyCode>>,
+    wave_counter: Res<WaveCounter>,
+    run_stats: Res<RunStats>,
+    balance: Res<balance::Balance>,
+    recorder: Res<replay::ReplayRecorder>,
+    playback: Option<Res<replay::ReplayPlayback>>,
+) {
+    if keyboard.just_pressed(KeyCode::Space) || keyboard.just_pressed(KeyCode::Escape) {
+        next_run_state.set(RunState::Playing);
     }
     if keyboard.just_pressed(KeyCode::Home) {
-        commands.remove_resource::<Paused>();
-        commands.insert_resource(Transition::new(Paused, MainMenu));
-    }
+        // An abandoned run still counts towards the lifetime statistics, the same as one that ended in death.
+        if playback.is_none() {
+            let score = run_stats.score(wave_counter.0, &balance.score);
+            let (_, replay_text) =
+                record_finished_run(&mut save_data, wave_counter.0, &run_stats, score, &recorder, false);
+            info!("{replay_text}");
+        }
+        next_screen.set(GameScreen::MainMenu);
+    }
+}
+
+/// Folds a run that's over into the save data and saves a replay of it.
+/// Returns whether it set a new best wave, along with a line saying where the replay went.
+fn record_finished_run(
+    save_data: &mut save::SaveData,
+    wave_reached: isize,
+    run_stats: &RunStats,
+    score: u64,
+    recorder: &replay::ReplayRecorder,
+    died: bool,
+) -> (bool, String) {
+    let new_best_wave = save_data.record_run(wave_reached, run_stats.enemies_killed, run_stats.play_time, score);
+    save::write_save_data(save_data);
+
+    let replay = replay::Replay {
+        seed: recorder.seed,
+        balance_fingerprint: recorder.balance_fingerprint,
+        inputs: recorder.inputs.clone(),
+        balance_reloads: recorder.balance_reloads.clone(),
+        outcome: simulation::SimulationOutcome {
+            seed: recorder.seed,
+            wave_reached,
+            enemies_killed: run_stats.enemies_killed,
+            ticks: recorder.ticks(),
+            died,
+        },
+    };
+    let replay_text = match replay::write_replay_file(&replay) {
+        Some(path) => format!("A replay of this run was saved to {}.", path.display()),
+        None => "The replay of this run couldn't be saved.".to_string(),
+    };
+
+    (new_best_wave, replay_text)
+}
+
+// Pausing virtual time means every `Res<Time>` reader sees a zero delta,
+// so timers and movement pick up exactly where they stopped.
+fn pause_time(mut time: ResMut<Time<Virtual>>) {
+    time.pause();
+}
+
+fn unpause_time(mut time: ResMut<Time<Virtual>>) {
+    time.unpause();
+}
+
+fn spawn_pause_overlay(
+    mut commands: Commands,
+    wave_counter: Res<WaveCounter>,
+    run_seed: Res<RunSeed>,
+    player_upgrade_counter: Res<PlayerUpgradeCounter>,
+    balance: Res<balance::Balance>,
+    player: Query<(&PlayerStats, &PlayerState, &Health), With<Player>>,
+) {
+    let (stats, state, health) = player.single();
+
+    commands.spawn((
+        StateScoped(RunState::Paused),
+        TextBundle::from_section(format!(
+            "Paused on wave {} (seed {})\n\n\
+            Health: {}/{}\n\
+            Weapon: {}\n\
+            Ranged damage: {}\n\
+            Ranged cooldown: {:.2}s\n\
+            Pierce: {}\n\
+            Projectile speed: {:.0}\n\
+            Movement speed: {:.0}\n\
+            End of round heal: {}\n\n\
+            {}\n\
+            Press Space or Escape to resume, or press Home to return to the main menu.",
+            wave_counter.0,
+            run_seed.0,
+            health.current_health(),
+            health.max_health(),
+            balance.weapons.definition(state.current_weapon).name,
+            stats.ranged_attack_damage,
+            stats.ranged_attack_cooldown,
+            stats.ranged_attack_pierce,
+            stats.ranged_attack_speed,
+            stats.movement_speed,
+            stats.end_of_round_heal,
+            player_upgrade_counter.display_text(),
+        ), TextStyle::default())
+            .with_background_color(Color::BLACK.with_alpha(0.7))
+            .with_style(Style {
+                position_type: PositionType::Absolute,
+                top: Val::Percent(15.0),
+                bottom: Val::Percent(15.0),
+                left: Val::Percent(20.0),
+                right: Val::Percent(20.0),
+                padding: UiRect::all(Val::Px(20.0)),
+                ..default()
+            }),
+    ));
 }
 
-fn handle_guide_input(mut commands: Commands, keyboard: Res<ButtonInput<KeyCode>>) {
+fn handle_guide_input(mut next_screen: ResMut<NextState<GameScreen>>, keyboard: Res<ButtonInput<KeyCode>>) {
     if keyboard.pressed(KeyCode::Escape) {
-        commands.remove_resource::<Guide>();
-        commands.insert_resource(Transition::new(Guide, MainMenu));
+        next_screen.set(GameScreen::MainMenu);
     }
 }
 
-fn handle_lose_screen_input(mut commands: Commands, keyboard: Res<ButtonInput<KeyCode>>) {
+fn handle_lose_screen_input(mut next_screen: ResMut<NextState<GameScreen>>, keyboard: Res<ButtonInput<KeyCode>>) {
     if keyboard.pressed(KeyCode::Enter) {
-        commands.remove_resource::<LoseScreen>();
-        commands.insert_resource(Transition::new(LoseScreen, Running))
+        next_screen.set(GameScreen::Running);
     }
 
     if keyboard.pressed(KeyCode::Escape) {
-        commands.remove_resource::<LoseScreen>();
-        commands.insert_resource(Transition::new(LoseScreen, MainMenu));
+        next_screen.set(GameScreen::MainMenu);
     }
 }
 
-fn start_on_menu(mut commands: Commands, high_score: Res<HighScore>) {
-    commands.insert_resource(MainMenu);
+fn spawn_main_menu(mut commands: Commands, save_data: Res<save::SaveData>, chosen_seed: Res<ChosenSeed>) {
+    let seed_text = match chosen_seed.0 {
+        Some(seed) => format!("\n\nEvery run will use the seed {seed}."),
+        None => String::new(),
+    };
+
     commands.spawn((
-        MainMenuItem,
+        StateScoped(GameScreen::MainMenu),
         TextBundle::from_section(format!(
             "Welcome to Circles Infinity!\n\n\
             In this game your goal is to survive endless waves of enemies for as long as possible.\n\n\
             To learn how to play, press the G key to view a guide.\n\n\
             If you know how to play, you can press the Enter/Return key to jump right into a game.\n\n\
-            The highest wave you've reached is {}.",
-            high_score.0
+            The highest wave you've reached is {}, and your best score is {}.\n\n\
+            Runs played: {}\n\
+            Enemies killed: {}\n\
+            Time played: {}{}",
+            save_data.best_wave,
+            save_data.best_score,
+            save_data.total_runs,
+            save_data.total_enemies_killed,
+            format_play_time(save_data.total_play_time),
+            seed_text,
         ), TextStyle::default())
             .with_style(Style {
                 position_type: PositionType::Absolute,
@@ -474,60 +1018,350 @@ fn start_on_menu(mut commands: Commands, high_score: Res<HighScore>) {
     ));
 }
 
-fn remove_dead_enemies(mut commands: Commands, mut query: Query<(Entity, &Health), With<Enemy>>) {
-    for (id, hp) in query.iter() {
+fn format_play_time(seconds: f64) -> String {
+    let seconds = seconds as u64;
+    format!("{}h {:02}m {:02}s", seconds / 3600, seconds / 60 % 60, seconds % 60)
+}
+
+fn tick_run_time(time: Res<Time>, mut run_stats: ResMut<RunStats>) {
+    run_stats.play_time += time.delta_seconds_f64();
+}
+
+fn remove_dead_enemies(
+    mut commands: Commands,
+    mut enemy_killed: EventWriter<EnemyKilled>,
+    mut player_upgrade_counter: ResMut<PlayerUpgradeCounter>,
+    mut rng: ResMut<GameRng>,
+    wave_counter: Res<WaveCounter>,
+    balance: Res<balance::Balance>,
+    query: Query<(Entity, &Health, &EnemyKind, &LastHitBy, &Transform), With<Enemy>>,
+) {
+    for (id, hp, kind, last_hit_by, transform) in query.iter() {
         if hp.current_health() == 0 {
             commands.entity(id).despawn();
+            enemy_killed.send(EnemyKilled { kind: *kind, position: transform.position(), killer: last_hit_by.0 });
+            match kind {
+                EnemyKind::Splitter => {
+                    split_enemy(&mut commands, transform.position(), wave_counter.0, &balance, &mut rng.0);
+                },
+                EnemyKind::Boss => {
+                    for _ in 0..balance.bosses.reward_upgrades {
+                        player_upgrade_counter.add_unused();
+                    }
+                },
+                _ => {},
+            }
         }
     }
 }
 
-fn update_player_health_text(mut player_health_text: Query<&mut Text, With<PlayerHealthText>>, player_health: Query<&Health, With<Player>>) {
-    let player_health = player_health.single();
-    *player_health_text.single_mut() = Text::from_section(
-        format!("Health: {}/{}", player_health.current_hp, player_health.max_hp),
-        TextStyle::default(),
-    );
+fn score_kills(
+    mut enemy_killed: EventReader<EnemyKilled>,
+    mut run_stats: ResMut<RunStats>,
+    balance: Res<balance::Balance>,
+) {
+    for killed in enemy_killed.read() {
+        run_stats.enemies_killed += 1;
+        run_stats.kill_score += balance.enemies.stats(killed.kind).score;
+        match killed.killer {
+            Some(DamageSource::Player(weapon)) => {
+                if run_stats.kills_by_weapon.len() <= weapon.0 {
+                    run_stats.kills_by_weapon.resize(weapon.0 + 1, 0);
+                }
+                run_stats.kills_by_weapon[weapon.0] += 1;
+            },
+            Some(DamageSource::CloseAttack) => run_stats.close_attack_kills += 1,
+            _ => {},
+        }
+    }
 }
 
-fn update_player(
-    time: Res<Time>,
-    mut camera_transform: Query<&mut Transform, With<Camera>>,
-    mut player: Query<(&mut Position, &mut Path, &PlayerStats, &mut PlayerState), With<Player>>,
-    keys: Res<ButtonInput<KeyCode>>,
+/// Lifesteal from upgrade cards, for every enemy the player kills themselves.
+fn heal_on_kill(
+    mut enemy_killed: EventReader<EnemyKilled>,
+    mut player: Query<(&Passives, &mut Health), With<Player>>,
 ) {
-    // update position
-    let w = keys.pressed(KeyCode::KeyW) || keys.pressed(KeyCode::ArrowUp);
-    let a = keys.pressed(KeyCode::KeyA) || keys.pressed(KeyCode::ArrowLeft);
-    let s = keys.pressed(KeyCode::KeyS) || keys.pressed(KeyCode::ArrowDown);
-    let d = keys.pressed(KeyCode::KeyD) || keys.pressed(KeyCode::ArrowRight);
-
-    let mut vertical_movement = if w && !s { 1f32 } else if s && !w { -1f32 } else { 0f32 };
-    let mut horizontal_movement = if d && !a { 1f32 } else if a && !d { -1f32 } else { 0f32 };
-    if vertical_movement != 0.0 && horizontal_movement != 0.0 {
-        let sqrt_2 = 2f32.sqrt();
-        vertical_movement /= sqrt_2;
-        horizontal_movement /= sqrt_2;
+    let (passives, mut health) = player.single_mut();
+    for killed in enemy_killed.read() {
+        if matches!(killed.killer, Some(DamageSource::Player(_) | DamageSource::CloseAttack)) {
+            health.heal(passives.heal_per_kill);
+        }
+    }
+}
+
+/// Leaves a quickly fading circle where each enemy died.
+fn spawn_death_puffs(
+    mut commands: Commands,
+    mut enemy_killed: EventReader<EnemyKilled>,
+    balance: Res<balance::Balance>,
+) {
+    for killed in enemy_killed.read() {
+        commands.spawn((
+            StateScoped(GameScreen::Running),
+            SplashResidue(Timer::from_seconds(0.3, TimerMode::Once)),
+            circle_shape(balance.enemies.stats(killed.kind).radius, killed.position),
+            Fill::color(killed.kind.color().with_alpha(0.4)),
+        ));
+    }
+}
+
+/// Breaks a dead splitter into a ring of weaker chasers where it died.
+fn split_enemy(commands: &mut Commands, pos: Vec2, wave: isize, balance: &balance::Balance, rng: &mut impl Rng) {
+    use num_traits::float::FloatConst;
+
+    let enemies = &balance.enemies;
+    let scaling = balance::StatScaling { health: enemies.split_health_fraction, ..default() };
+    let spread = enemies.splitter.radius;
+    for i in 0..enemies.split_count {
+        let angle = i as f32 / enemies.split_count as f32 * f32::PI() * 2.0;
+        let offset = Vec2::new(angle.cos(), angle.sin()) * spread;
+        spawn_enemy(commands, EnemyKind::Chaser, pos + offset, wave, scaling, balance, rng);
+    }
+}
+
+fn update_player_health_text(mut player_health_text: Query<&mut Text, With<PlayerHealthText>>, player_health: Query<&Health, With<Player>>) {
+    let player_health = player_health.single();
+    *player_health_text.single_mut() = Text::from_section(
+        format!("Health: {}/{}", player_health.current_hp, player_health.max_hp),
+        TextStyle::default(),
+    );
+}
+
+fn update_close_attack_text(
+    mut close_attack_text: Query<&mut Text, With<CloseAttackText>>,
+    player: Query<&PlayerState, With<Player>>,
+) {
+    let timer = &player.single().close_attack_timer;
+    let text = if timer.finished() {
+        "Shockwave: ready".to_string()
+    } else {
+        format!("Shockwave: {:.1}s", timer.remaining_secs())
+    };
+    *close_attack_text.single_mut() = Text::from_section(text, TextStyle::default());
+}
+
+fn update_weapon_text(
+    mut weapon_text: Query<&mut Text, With<WeaponText>>,
+    player: Query<&PlayerState, With<Player>>,
+    wave_counter: Res<WaveCounter>,
+    balance: Res<balance::Balance>,
+) {
+    let weapons = &balance.weapons;
+    let state = player.single();
+    let current = state.current_weapon;
+    let unlocked = (0..weapons.roster.len())
+        .filter(|index| weapons.is_unlocked(PlayerWeapon(*index), wave_counter.0))
+        .count();
+    let text = format!(
+        "Weapon: {} level {} ({} unlocked, Q and E to switch)",
+        weapons.definition(current).name,
+        state.taken_upgrades(current).len() + 1,
+        unlocked,
+    );
+    *weapon_text.single_mut() = Text::from_section(text, TextStyle::default());
+}
+
+/// Deals the cards on offer out as buttons, which can be clicked on as well as picked with the number keys.
+fn update_draft_panel(
+    mut commands: Commands,
+    mut panel: Query<(Entity, &mut Visibility), With<DraftPanel>>,
+    draft: Res<Draft>,
+    balance: Res<balance::Balance>,
+) {
+    let Ok((panel, mut visibility)) = panel.get_single_mut() else { return };
+    commands.entity(panel).despawn_descendants();
+    if draft.offer.is_empty() {
+        *visibility = Visibility::Hidden;
+        return;
+    }
+    *visibility = Visibility::Inherited;
+
+    commands.entity(panel).with_children(|panel| {
+        for (slot, card) in draft.offer.iter().filter_map(|index| balance.draft.cards.get(*index)).enumerate() {
+            let background = match card.rarity {
+                balance::Rarity::Common => SLATE_700,
+                balance::Rarity::Rare => BLUE_800,
+                balance::Rarity::Legendary => ORANGE_700,
+            };
+            panel.spawn((
+                DraftCardButton(slot),
+                ButtonBundle {
+                    style: Style {
+                        width: Val::Px(220.0),
+                        padding: UiRect::all(Val::Px(10.0)),
+                        ..default()
+                    },
+                    background_color: BackgroundColor(background.into()),
+                    ..default()
+                },
+            )).with_children(|button| {
+                let text = format!(
+                    "[{}] {} ({:?})\n{}",
+                    slot + 1,
+                    card.name,
+                    card.rarity,
+                    card.describe(&balance.upgrades),
+                );
+                button.spawn(TextBundle::from_section(text, TextStyle { font_size: 18.0, ..default() }));
+            });
+        }
+
+        panel.spawn(
+            TextBundle::from_section(
+                format!(
+                    "Click or press 1-3 to pick. [R] Reroll ({} left), \
+                    [Shift] or right click to banish ({} left)",
+                    draft.rerolls_left,
+                    draft.banishes_left,
+                ),
+                TextStyle { font_size: 18.0, ..default() },
+            )
+            .with_style(Style { width: Val::Percent(100.0), ..default() })
+            .with_text_justify(JustifyText::Center),
+        );
+    });
+}
+
+/// Between waves, shows what the current weapon has been upgraded with and what it can take next.
+fn update_upgrade_screen(
+    mut screen: Query<(&mut Text, &mut Visibility), With<UpgradeScreen>>,
+    player: Query<&PlayerState, With<Player>>,
+    intermission: Res<Intermission>,
+    player_upgrade_counter: Res<PlayerUpgradeCounter>,
+    balance: Res<balance::Balance>,
+) {
+    let (mut text, mut visibility) = screen.single_mut();
+    if intermission.0.is_none() {
+        *visibility = Visibility::Hidden;
+        return;
+    }
+    *visibility = Visibility::Inherited;
+
+    let state = player.single();
+    let weapon = state.current_weapon;
+    let definition = balance.weapons.definition(weapon);
+    let taken = state.taken_upgrades(weapon);
+    let describe = |upgrade: &balance::WeaponUpgrade| {
+        let effects = upgrade.effects.iter().map(balance::UpgradeEffect::describe).collect::<Vec<_>>();
+        format!("{}: {}", upgrade.name, effects.join(", "))
+    };
+
+    let mut lines = vec![format!("{} upgrades (level {})", definition.name, taken.len() + 1)];
+    lines.extend(
+        taken.iter()
+            .filter_map(|index| definition.upgrades.get(*index))
+            .map(|upgrade| format!("Taken - {}", describe(upgrade))),
+    );
+    let next = definition.next_upgrades(taken);
+    if next.is_empty() {
+        lines.push("Fully upgraded.".to_string());
+    } else if player_upgrade_counter.unused_upgrades == 0 {
+        lines.push("Earn another upgrade to pick one of:".to_string());
+    } else {
+        lines.push("Pick one, the others will be gone for good:".to_string());
     }
+    // Keys 4 to 9 pick between them.
+    lines.extend(next.iter().take(6).enumerate().map(|(choice, index)| {
+        format!("[{}] {}", choice + 4, describe(&definition.upgrades[*index]))
+    }));
+    lines.push("Press Q or E to look at another weapon.".to_string());
+
+    *text = Text::from_section(lines.join("\n"), TextStyle::default());
+}
+
+fn update_score_text(
+    mut score_text: Query<&mut Text, With<ScoreText>>,
+    run_stats: Res<RunStats>,
+    wave_counter: Res<WaveCounter>,
+    balance: Res<balance::Balance>,
+) {
+    let score = run_stats.score(wave_counter.0, &balance.score);
+    *score_text.single_mut() = Text::from_section(format!("Score: {score}"), TextStyle::default());
+}
+
+fn update_intermission_text(
+    mut intermission_text: Query<&mut Text, With<IntermissionText>>,
+    intermission: Res<Intermission>,
+    balance: Res<balance::Balance>,
+) {
+    let text = match intermission.0 {
+        Some(time_left) => format!(
+            "Next wave in {:.1}s\nPress Space to call it now for {} points",
+            time_left,
+            (time_left * balance.waves.early_call_score_per_second) as u64,
+        ),
+        None => String::new(),
+    };
+    *intermission_text.single_mut() = Text::from_section(text, TextStyle::default());
+}
+
+fn update_boss_health_bar(
+    mut bar: Query<&mut Visibility, With<BossHealthBar>>,
+    mut fill: Query<&mut Style, With<BossHealthBarFill>>,
+    boss: Query<&Health, With<Boss>>,
+) {
+    let mut visibility = bar.single_mut();
+    let Ok(boss_health) = boss.get_single() else {
+        *visibility = Visibility::Hidden;
+        return;
+    };
+    *visibility = Visibility::Inherited;
+    let health_percent = boss_health.current_health() as f32 / boss_health.max_health() as f32 * 100.0;
+    fill.single_mut().width = Val::Percent(health_percent);
+}
+
+fn update_wave_counter_text(mut wave_counter_text: Query<&mut Text, With<WaveCounterText>>, wave_counter: Res<WaveCounter>) {
+    *wave_counter_text.single_mut() = Text::from_section(format!("Wave {}", wave_counter.0), TextStyle::default());
+}
+
+fn update_player_upgrade_counter_text(
+    mut player_upgrade_counter_text: Query<&mut Text, With<PlayerUpgradeCounterText>>,
+    player_upgrade_counter: Res<PlayerUpgradeCounter>,
+) {
+    *player_upgrade_counter_text.single_mut() =
+        Text::from_section(player_upgrade_counter.display_text(), TextStyle::default());
+}
 
-    let (mut pos, mut path, stats, mut state) = player.single_mut();
+fn follow_player_with_camera(
+    time: Res<Time>,
+    mut camera_transform: Query<&mut Transform, (With<Camera>, Without<Player>)>,
+    player: Query<&Transform, With<Player>>,
+) {
+    let pos = player.single().position();
     let mut transform = camera_transform.single_mut();
     let dt = time.delta_seconds();
+    transform.translation = transform.translation.lerp(pos.extend(transform.translation.z), dt * 3.0);
+}
+
+fn pulse_power_ups(mut query: Query<(&PowerUp, &mut Transform)>) {
+    for (power_up, mut transform) in query.iter_mut() {
+        let scale = power_up_radius(power_up.time_since_created) / POWER_UP_RADIUS;
+        transform.scale = Vec3::new(scale, scale, 1.0);
+    }
+}
+
+fn update_player(
+    time: Res<Time>,
+    player_input: Res<PlayerInput>,
+    balance: Res<balance::Balance>,
+    mut player: Query<(&mut Transform, &PlayerStats, &mut PlayerState), With<Player>>,
+) {
+    let (mut transform, stats, mut state) = player.single_mut();
+    let dt = time.delta_seconds();
 
     if state.boost_time_left > 0.0 {
         state.boost_time_left -= time.delta_seconds();
         state.boost_time_left = f32::max(state.boost_time_left, 0.0);
     }
 
-    let speed_multiplier: f32 = dt * if state.boost_time_left > 0.0 { 2.0 } else { 1.0 };
-    pos.y += vertical_movement * speed_multiplier * stats.movement_speed;
-    pos.x += horizontal_movement * speed_multiplier * stats.movement_speed;
-
-    *path = circle!(PLAYER_RADIUS, *pos).path;
-    transform.translation = transform.translation.lerp(Vec3::new(pos.x, pos.y, transform.translation.z), dt * 3.0);
+    // update position
+    let movement = player_input.movement.clamp_length_max(1.0);
+    let boost_multiplier = balance.power_ups.boost_speed_multiplier;
+    let speed_multiplier: f32 = if state.boost_time_left > 0.0 { boost_multiplier } else { 1.0 };
+    state.velocity = movement * speed_multiplier * stats.movement_speed;
+    transform.translate_by(state.velocity * dt);
 
     // update timers
-    if state.close_attack_timer.finished() { state.close_attack_timer.reset() }
     if state.ranged_attack_timer.finished() { state.ranged_attack_timer.reset() }
 
     let dt = time.delta();
@@ -535,27 +1369,133 @@ fn update_player(
     state.ranged_attack_timer.tick(dt);
 }
 
+/// Health regeneration from upgrade cards, which builds up a fraction of a hit point at a time.
+fn regenerate_health(time: Res<Time>, mut player: Query<(&mut Passives, &mut Health), With<Player>>) {
+    let (mut passives, mut health) = player.single_mut();
+    passives.regeneration_progress += passives.regeneration * time.delta_seconds();
+    let whole = passives.regeneration_progress.floor();
+    if whole >= 1.0 {
+        passives.regeneration_progress -= whole;
+        health.heal(whole as usize);
+    }
+}
+
 fn do_splashes(
     mut commands: Commands,
-    mut enemies: Query<(&mut Health, &Position), With<Enemy>>,
-    splashes: Query<(Entity, &SplashProjectileResidual, &Position)>,
+    mut damage_events: EventWriter<DamageEvent>,
+    spatial_index: Res<spatial::SpatialIndex>,
+    splashes: Query<(Entity, &SplashProjectileResidual, &Transform)>,
 ) {
-    splashes.iter().for_each(|(id, residual, splash_pos)| {
-        enemies.iter_mut().for_each(|(mut enemy_hp, enemy_pos)| {
-            if to_vec2!(splash_pos).distance(to_vec2!(enemy_pos)) < ENEMY_RADIUS + residual.radius {
-                enemy_hp.damage(residual.damage);
+    splashes.iter().for_each(|(id, residual, splash_transform)| {
+        let splash_pos = splash_transform.position();
+        for enemy in spatial_index.enemies.candidates(splash_pos, residual.radius) {
+            if splash_pos.distance(enemy.position) < enemy.radius + residual.radius {
+                damage_events.send(DamageEvent {
+                    target: enemy.entity,
+                    amount: residual.damage,
+                    source: DamageSource::Player(residual.weapon),
+                    kind: DamageKind::Splash,
+                });
             }
-        });
+        }
         commands.spawn((
-            RunningObject,
+            StateScoped(GameScreen::Running),
             SplashResidue(Timer::from_seconds(0.5, TimerMode::Once)),
-            circle!(residual.radius * 0.6, splash_pos),
+            circle_shape(residual.radius * 0.6, splash_pos),
             Fill::color(PURPLE.with_alpha(0.3)),
         ));
         commands.entity(id).despawn();
     })
 }
 
+/// Applies all the damage dealt since it last ran.
+/// It runs once a tick, before dead enemies are removed,
+/// so damage dealt later in the tick, like enemy melee attacks, lands at the start of the next one.
+fn apply_damage(
+    mut commands: Commands,
+    mut damage_events: EventReader<DamageEvent>,
+    mut run_stats: ResMut<RunStats>,
+    mut targets: Query<(&mut Health, Option<&mut LastHitBy>, Has<Player>)>,
+) {
+    for damage in damage_events.read() {
+        let Ok((mut health, last_hit_by, is_player)) = targets.get_mut(damage.target) else { continue };
+        health.damage(damage.amount);
+
+        if is_player {
+            run_stats.damage_taken += damage.amount as u64;
+        } else {
+            run_stats.damage_dealt += damage.amount as u64;
+            commands.entity(damage.target).insert(HitFlash(Timer::from_seconds(0.12, TimerMode::Once)));
+        }
+        if let Some(mut last_hit_by) = last_hit_by.filter(|_| damage.source != DamageSource::Enemy) {
+            last_hit_by.0 = Some(damage.source);
+        }
+    }
+}
+
+/// Shows how much damage each hit did, floating up from whatever took it.
+fn spawn_damage_numbers(
+    mut commands: Commands,
+    mut damage_events: EventReader<DamageEvent>,
+    targets: Query<(&Transform, Has<Player>)>,
+) {
+    for damage in damage_events.read() {
+        let Ok((transform, is_player)) = targets.get(damage.target) else { continue };
+        let color = match (is_player, damage.kind) {
+            (true, _) => RED,
+            (false, DamageKind::Splash) => PURPLE,
+            (false, DamageKind::Chain) => SKY_300,
+            (false, _) => WHITE,
+        };
+        commands.spawn((
+            StateScoped(GameScreen::Running),
+            DamageNumber(Timer::from_seconds(0.6, TimerMode::Once)),
+            Text2dBundle {
+                text: Text::from_section(
+                    damage.amount.to_string(),
+                    TextStyle { font_size: 20.0, color: color.into(), ..default() },
+                ),
+                transform: Transform::from_translation(transform.position().extend(10.0)),
+                ..default()
+            },
+        ));
+    }
+}
+
+fn float_damage_numbers(
+    time: Res<Time>,
+    mut commands: Commands,
+    mut numbers: Query<(Entity, &mut DamageNumber, &mut Transform, &mut Text)>,
+) {
+    for (id, mut number, mut transform, mut text) in numbers.iter_mut() {
+        number.0.tick(time.delta());
+        if number.0.finished() {
+            commands.entity(id).despawn();
+            continue;
+        }
+        transform.translate_by(Vec2::Y * DAMAGE_NUMBER_RISE_SPEED * time.delta_seconds());
+        for section in &mut text.sections {
+            section.style.color.set_alpha(number.0.fraction_remaining());
+        }
+    }
+}
+
+fn fade_hit_flashes(
+    time: Res<Time>,
+    mut commands: Commands,
+    mut flashes: Query<(Entity, &EnemyKind, &mut HitFlash, &mut Fill)>,
+) {
+    for (id, kind, mut flash, mut fill) in flashes.iter_mut() {
+        flash.0.tick(time.delta());
+        if flash.0.finished() {
+            fill.color = kind.color().into();
+            commands.entity(id).remove::<HitFlash>();
+        } else {
+            fill.color = WHITE.mix(&kind.color(), flash.0.fraction()).into();
+        }
+    }
+}
+
 fn handle_residue(time: Res<Time>, mut commands: Commands, mut residue: Query<(Entity, &mut SplashResidue)>) {
     let dt = time.delta();
     for (id, mut residue) in residue.iter_mut() {
@@ -569,118 +1509,694 @@ fn handle_residue(time: Res<Time>, mut commands: Commands, mut residue: Query<(E
 fn enemy_update_and_attack(
     time: Res<Time>,
     mut commands: Commands,
-    mut player: Query<(&Position, &mut Health), (With<Player>, Without<Enemy>)>,
-    mut query: Query<(Entity, &EnemyStats, &mut EnemyState, &mut Position, &mut Path), (With<Enemy>, Without<Player>)>,
+    spatial_index: Res<spatial::SpatialIndex>,
+    balance: Res<balance::Balance>,
+    mut damage_events: EventWriter<DamageEvent>,
+    player: Query<(Entity, &Transform, &PlayerState), (With<Player>, Without<Enemy>)>,
+    mut query: Query<
+        (Entity, &EnemyKind, &EnemyStats, &mut EnemyState, &mut Transform),
+        (With<Enemy>, Without<Player>),
+    >,
 ) {
-    let (player_pos, mut player_hp) = player.single_mut();
-    let dt = time.delta();
-    for (id, stats, mut state, mut pos, mut path) in query.iter_mut() {
+    let (player_id, player_transform, player_state) = player.single();
+    let player_pos = player_transform.position();
+    let in_melee_range = spatial_index.enemies.candidates(player_pos, PLAYER_RADIUS).into_iter()
+        .filter(|enemy| enemy.position.distance(player_pos) <= enemy.radius + PLAYER_RADIUS)
+        .map(|enemy| enemy.entity)
+        .collect::<Vec<_>>();
 
-        if state.close_attack_timer.finished() && pos.distance(player_pos) <= ENEMY_RADIUS + PLAYER_RADIUS {
-            player_hp.damage(stats.close_attack_damage);
+    let dt = time.delta();
+    for (id, kind, stats, mut state, mut transform) in query.iter_mut() {
+
+        if state.close_attack_timer.finished() && in_melee_range.contains(&id) {
+            damage_events.send(DamageEvent {
+                target: player_id,
+                amount: stats.close_attack_damage,
+                source: DamageSource::Enemy,
+                kind: DamageKind::Melee,
+            });
             state.close_attack_timer.reset();
         } else { state.close_attack_timer.tick(dt); }
+        state.ability_timer.tick(dt);
 
-        let base_movement = (to_vec2!((pos.x, pos.y)) - to_vec2!(player_pos)).normalize_or_zero();
-        let move_vector = -stats.movement_speed * base_movement * time.delta_seconds();
-        *pos = (to_vec2!((pos.x, pos.y)) + (move_vector)).into();
-        *path = circle!(ENEMY_RADIUS, *pos).path;
+        let to_player = (player_pos - transform.position()).normalize_or_zero();
+        let player_distance = transform.position().distance(player_pos);
+        let separation = enemy_separation(id, transform.position(), stats.separation_radius, &spatial_index.enemies);
+        let velocity = enemy_velocity(
+            *kind, stats, &mut state, to_player, player_distance, separation, &balance.enemies, time.delta_seconds(),
+        );
+        transform.translate_by((velocity + state.knockback_velocity) * time.delta_seconds());
+        state.knockback_velocity *= 0.5f32.powf(time.delta_seconds() / KNOCKBACK_HALF_LIFE);
 
         if stats.ranged_attack_damage > 0 && state.ranged_attack_timer.finished() {
-            commands.spawn((
-                RunningObject,
-                EnemyProjectile,
-                Projectile {
-                    damage: stats.ranged_attack_damage,
-                    velocity: -base_movement * stats.ranged_attack_speed,
-                    location: to_vec2!(*pos),
-                    radius: BASE_PROJECTILE_RADIUS,
-                    pierce_left: 1,
-                    last_entity_hit: id,
-                },
-                circle!(BASE_PROJECTILE_RADIUS, *pos),
-                Fill::color(ORANGE_RED),
-                Stroke::new(BLACK, 5f32),
-            ));
+            fire_shot_pattern(&mut commands, stats, transform.position(), player_pos, player_state.velocity);
             state.ranged_attack_timer.reset();
         } else { state.ranged_attack_timer.tick(dt); }
     }
 }
 
-fn resolve_player_projectiles(
+/// Fires one volley of an enemy's shot pattern from `pos`.
+fn fire_shot_pattern(commands: &mut Commands, stats: &EnemyStats, pos: Vec2, player_pos: Vec2, player_velocity: Vec2) {
+    use balance::ShotPattern;
+    use num_traits::float::FloatConst;
+
+    let speed = stats.ranged_attack_speed;
+    let damage = stats.ranged_attack_damage;
+    let aim = (player_pos - pos).normalize_or_zero();
+    match stats.shot_pattern {
+        ShotPattern::Aimed => {
+            spawn_enemy_projectile(commands, pos, aim * speed, damage);
+        },
+        ShotPattern::Leading => {
+            let direction = lead_direction(player_pos - pos, player_velocity, speed).unwrap_or(aim);
+            spawn_enemy_projectile(commands, pos, direction * speed, damage);
+        },
+        ShotPattern::Fan { count, spread } => {
+            for direction in fan_directions(aim, count, spread) {
+                spawn_enemy_projectile(commands, pos, direction * speed, damage);
+            }
+        },
+        ShotPattern::Radial { count } => {
+            for i in 0..count {
+                let angle = i as f32 / count as f32 * f32::PI() * 2.0;
+                let direction = Vec2::from_angle(angle).rotate(aim);
+                spawn_enemy_projectile(commands, pos, direction * speed, damage);
+            }
+        },
+        ShotPattern::Homing { speed_multiplier, turn_rate, lifetime } => {
+            let id = spawn_enemy_projectile(commands, pos, aim * speed * speed_multiplier, damage);
+            commands.entity(id).insert(Homing { turn_rate, time_left: lifetime });
+        },
+        ShotPattern::Mine { fuse, radius } => {
+            commands.spawn((
+                StateScoped(GameScreen::Running),
+                Mine { damage, radius, fuse: Timer::from_seconds(fuse, TimerMode::Once) },
+                circle_shape(radius, pos),
+                Fill::color(ORANGE_RED.with_alpha(0.15)),
+                Stroke::new(ORANGE_RED, 2.0),
+            ));
+        },
+    }
+}
+
+/// `count` directions spread evenly over `spread` radians, centred on `aim`.
+fn fan_directions(aim: Vec2, count: usize, spread: f32) -> impl Iterator<Item = Vec2> {
+    (0..count).map(move |i| {
+        let along = if count > 1 { i as f32 / (count - 1) as f32 - 0.5 } else { 0.0 };
+        Vec2::from_angle(along * spread).rotate(aim)
+    })
+}
+
+/// The direction to fire a shot moving at `speed` so that it meets a target at `offset`
+/// that keeps moving at `target_velocity`, or `None` if the shot can never catch up.
+fn lead_direction(offset: Vec2, target_velocity: Vec2, speed: f32) -> Option<Vec2> {
+    // Solves |offset + target_velocity * t| = speed * t for the earliest t after now.
+    let a = target_velocity.length_squared() - speed * speed;
+    let b = 2.0 * offset.dot(target_velocity);
+    let c = offset.length_squared();
+    let t = if a.abs() < f32::EPSILON {
+        -c / b
+    } else {
+        let discriminant = b * b - 4.0 * a * c;
+        if discriminant < 0.0 {
+            return None;
+        }
+        let roots = [(-b - discriminant.sqrt()) / (2.0 * a), (-b + discriminant.sqrt()) / (2.0 * a)];
+        roots.into_iter().filter(|t| *t > 0.0).min_by(f32::total_cmp)?
+    };
+    (t > 0.0).then(|| (offset + target_velocity * t).normalize_or_zero())
+}
+
+/// Turns homing shots towards the player, and gets rid of them once they've run out of time.
+fn steer_homing_projectiles(
+    time: Res<Time>,
     mut commands: Commands,
-    mut enemies: Query<(Entity, &Position, &mut Health), With<Enemy>>,
-    mut query: Query<(Entity, &mut Projectile, Option<&SplashProjectile>), With<PlayerProjectile>>,
+    player: Query<&Transform, With<Player>>,
+    mut projectiles: Query<(Entity, &mut Homing, &mut Projectile, &Transform), Without<Player>>,
 ) {
+    let player_pos = player.single().position();
+    let dt = time.delta_seconds();
+    for (id, mut homing, mut projectile, transform) in projectiles.iter_mut() {
+        homing.time_left -= dt;
+        if homing.time_left <= 0.0 {
+            commands.entity(id).despawn();
+            continue;
+        }
 
-    let mut enemies = enemies.iter_mut()
-        .map(|(id, loc, hp)| (id, to_vec2!((loc.x, loc.y)), hp))
-        .collect::<Vec<_>>();
+        let wanted = (player_pos - transform.position()).normalize_or_zero();
+        let turn = projectile.velocity.angle_between(wanted);
+        if turn.is_finite() {
+            let max_turn = homing.turn_rate * dt;
+            projectile.velocity = Vec2::from_angle(turn.clamp(-max_turn, max_turn)).rotate(projectile.velocity);
+        }
+    }
+}
+
+/// Blows up mines once their fuse runs out, hurting the player if they're caught in the blast.
+fn detonate_mines(
+    time: Res<Time>,
+    mut commands: Commands,
+    mut damage_events: EventWriter<DamageEvent>,
+    player: Query<(Entity, &Transform), With<Player>>,
+    mut mines: Query<(Entity, &mut Mine, &Transform), Without<Player>>,
+) {
+    let (player_id, player_transform) = player.single();
+    let player_pos = player_transform.position();
+    for (id, mut mine, transform) in mines.iter_mut() {
+        if !mine.fuse.tick(time.delta()).finished() {
+            continue;
+        }
 
-    for (id, mut projectile, splash_stats) in query.iter_mut() {
-        let approx_x = projectile.location.x as isize;
-        let approx_y = projectile.location.y as isize;
-        let collide_distance: isize = ENEMY_RADIUS as isize + projectile.radius as isize;
+        let mine_pos = transform.position();
+        if mine_pos.distance(player_pos) < mine.radius + PLAYER_RADIUS {
+            damage_events.send(DamageEvent {
+                target: player_id,
+                amount: mine.damage,
+                source: DamageSource::Enemy,
+                kind: DamageKind::Splash,
+            });
+        }
+        commands.spawn((
+            StateScoped(GameScreen::Running),
+            SplashResidue(Timer::from_seconds(0.3, TimerMode::Once)),
+            circle_shape(mine.radius, mine_pos),
+            Fill::color(ORANGE_RED.with_alpha(0.5)),
+        ));
+        commands.entity(id).despawn();
+    }
+}
 
-        for (enemy_id, enemy_loc, ref mut enemy_health) in enemies.iter_mut() {
-            if *enemy_id == projectile.last_entity_hit {
-                continue;
+fn spawn_enemy_projectile(commands: &mut Commands, pos: Vec2, velocity: Vec2, damage: usize) -> Entity {
+    commands.spawn((
+        StateScoped(GameScreen::Running),
+        EnemyProjectile,
+        Projectile {
+            damage,
+            velocity,
+            radius: BASE_PROJECTILE_RADIUS,
+            pierce_left: 1,
+            already_hit: Vec::new(),
+        },
+        circle_shape(BASE_PROJECTILE_RADIUS, pos),
+        Fill::color(ORANGE_RED),
+        Stroke::new(BLACK, 5f32),
+    )).id()
+}
+
+/// Fires whichever of the boss's attacks are ready and allowed in its current phase.
+/// Charges are only started here, `enemy_update_and_attack` moves the boss through them.
+fn boss_attacks(
+    time: Res<Time>,
+    mut commands: Commands,
+    mut rng: ResMut<GameRng>,
+    wave_counter: Res<WaveCounter>,
+    balance: Res<balance::Balance>,
+    player: Query<&Transform, (With<Player>, Without<Boss>)>,
+    mut bosses: Query<(&mut Boss, &mut EnemyState, &EnemyStats, &Health, &Transform), Without<Player>>,
+) {
+    use num_traits::float::FloatConst;
+
+    let player_pos = player.single().position();
+    let bosses_balance = &balance.bosses;
+    let dt = time.delta();
+    for (mut boss, mut state, stats, health, transform) in bosses.iter_mut() {
+        let boss_pos = transform.position();
+        let phase = bosses_balance.phase(health.current_health() as f32 / health.max_health() as f32);
+
+        if boss.ring_timer.tick(dt).just_finished() {
+            // Every other ring is turned by half a gap, so standing still between bullets doesn't work twice.
+            let count = bosses_balance.ring_bullet_count;
+            let turn = if boss.rings_fired % 2 == 0 { 0.0 } else { 0.5 };
+            for i in 0..count {
+                let angle = (i as f32 + turn) / count as f32 * f32::PI() * 2.0;
+                let direction = Vec2::new(angle.cos(), angle.sin());
+                let velocity = direction * bosses_balance.ring_bullet_speed;
+                spawn_enemy_projectile(&mut commands, boss_pos + direction * stats.radius, velocity, boss.ring_damage);
+            }
+            boss.rings_fired += 1;
+        }
+
+        if boss.summon_timer.tick(dt).just_finished() && phase >= 2 {
+            let count = bosses_balance.summon_count;
+            let spread = stats.radius + balance.enemies.chaser.radius;
+            for i in 0..count {
+                let angle = i as f32 / count as f32 * f32::PI() * 2.0;
+                let pos = boss_pos + Vec2::new(angle.cos(), angle.sin()) * spread;
+                let scaling = balance::StatScaling::default();
+                spawn_enemy(&mut commands, EnemyKind::Chaser, pos, wave_counter.0, scaling, &balance, &mut rng.0);
             }
+        }
+
+        if boss.charge_timer.tick(dt).just_finished() && phase >= 3 {
+            let to_player = (player_pos - boss_pos).normalize_or_zero();
+            let velocity = to_player * stats.movement_speed * bosses_balance.charge_speed_multiplier;
+            state.start_dash(velocity, bosses_balance.charge_duration);
+        }
+    }
+}
 
-            let approx_enemy_x = enemy_loc.x as isize;
-            let approx_enemy_y = enemy_loc.y as isize;
+/// How fast and which way an enemy moves this tick, depending on its kind.
+/// `to_player` is the direction to the player, and `separation` the push away from other enemies.
+fn enemy_velocity(
+    kind: EnemyKind,
+    stats: &EnemyStats,
+    state: &mut EnemyState,
+    to_player: Vec2,
+    player_distance: f32,
+    separation: Vec2,
+    enemies: &balance::EnemyBalance,
+    dt: f32,
+) -> Vec2 {
+    if kind == EnemyKind::Dasher
+        && state.dash_time_left <= 0.0
+        && state.ability_timer.finished()
+        && player_distance <= enemies.dash_range
+    {
+        state.start_dash(to_player * stats.movement_speed * enemies.dash_speed_multiplier, enemies.dash_duration);
+        state.ability_timer.reset();
+    }
+    if state.dash_time_left > 0.0 {
+        state.dash_time_left -= dt;
+        return state.dash_velocity;
+    }
+
+    let heading = match kind {
+        EnemyKind::Shooter if player_distance < enemies.shooter_distance * 0.8 => -to_player,
+        EnemyKind::Shooter if player_distance <= enemies.shooter_distance => Vec2::ZERO,
+        _ => to_player,
+    };
+    (heading + separation * stats.separation_strength).normalize_or_zero() * stats.movement_speed
+}
+
+/// Every healer that's ready heals the other enemies around it.
+/// Runs after enemies have moved, but uses where they were at the start of the tick like separation does.
+fn heal_nearby_enemies(
+    mut commands: Commands,
+    spatial_index: Res<spatial::SpatialIndex>,
+    balance: Res<balance::Balance>,
+    mut healers: Query<(Entity, &EnemyKind, &mut EnemyState, &Transform)>,
+    mut enemies: Query<&mut Health, With<Enemy>>,
+) {
+    let enemy_balance = &balance.enemies;
+    for (id, kind, mut state, transform) in healers.iter_mut() {
+        if *kind != EnemyKind::Healer || !state.ability_timer.finished() {
+            continue;
+        }
+        state.ability_timer.reset();
 
-            if (approx_enemy_x - approx_x).abs() > collide_distance
-            || (approx_enemy_y - approx_y).abs() > collide_distance {
+        let healer_pos = transform.position();
+        for other in spatial_index.enemies.candidates(healer_pos, enemy_balance.heal_radius) {
+            if other.entity == id || other.position.distance(healer_pos) > enemy_balance.heal_radius {
                 continue;
             }
+            if let Ok(mut other_hp) = enemies.get_mut(other.entity) {
+                let amount = (other_hp.max_health() as f32 * enemy_balance.heal_fraction) as usize;
+                other_hp.heal(amount.max(1));
+            }
+        }
+        commands.spawn((
+            StateScoped(GameScreen::Running),
+            SplashResidue(Timer::from_seconds(0.5, TimerMode::Once)),
+            circle_shape(enemy_balance.heal_radius, healer_pos),
+            Fill::color(EnemyKind::Healer.color().with_alpha(0.15)),
+        ));
+    }
+}
 
-            if projectile.location.distance(*enemy_loc) as isize <= collide_distance {
-                enemy_health.damage(projectile.damage);
-                if let Some(splash_stats) = splash_stats {
-                    commands.spawn((
-                        RunningObject,
-                        SplashProjectileResidual {
-                            damage: splash_stats.damage,
-                            radius: splash_stats.range,
-                        },
-                        Position::new(projectile.location.x, projectile.location.y),
-                    ));
-                }
+/// Pushes an enemy away from every other enemy within `radius`, more strongly the closer they are,
+/// so a wave spreads out around the player instead of piling up on one spot.
+/// Uses where everyone was at the start of the tick, so it doesn't matter which enemy moves first.
+fn enemy_separation(id: Entity, position: Vec2, radius: f32, enemies: &spatial::SpatialGrid) -> Vec2 {
+    let mut push = Vec2::ZERO;
+    for other in enemies.candidates(position, radius) {
+        let distance = other.position.distance(position);
+        if other.entity == id || distance >= radius {
+            continue;
+        }
+        push += (position - other.position).normalize_or_zero() * (1.0 - distance / radius);
+    }
+    push
+}
 
-                match projectile.pierce_left {
-                    0 => commands.entity(id).despawn(),
-                    _ => projectile.pierce_left -= 1,
-                }
-                projectile.last_entity_hit = *enemy_id;
-            }
+/// Projectiles are checked along the whole path they're about to take this tick,
+/// so fast ones can't skip over enemies, and enemies are hit in the order
+/// the projectile reaches them so pierce is used up on the right ones.
+fn resolve_player_projectiles(
+    time: Res<Time>,
+    mut commands: Commands,
+    spatial_index: Res<spatial::SpatialIndex>,
+    mut damage_events: EventWriter<DamageEvent>,
+    mut projectile_hits: EventWriter<ProjectileHit>,
+    mut query: Query<(Entity, &PlayerProjectile, &mut Projectile, &Transform)>,
+) {
+    let dt = time.delta_seconds();
+    for (id, player_projectile, mut projectile, transform) in query.iter_mut() {
+        let start = transform.position();
+        let end = start + projectile.velocity * dt;
+
+        let reached = spatial_index.enemies.candidates_along(start, end, projectile.radius).into_iter()
+            .filter_map(|enemy| {
+                let collide_distance = enemy.radius + projectile.radius;
+                spatial::sweep_circle(start, end, enemy.position, collide_distance).map(|along| (along, enemy.entity))
+            });
+        let (hits, spent) = pierce_through(&mut projectile, reached);
+
+        let hit_count = hits.len();
+        for (index, (along, enemy)) in hits.into_iter().enumerate() {
+            damage_events.send(DamageEvent {
+                target: enemy,
+                amount: projectile.damage,
+                source: DamageSource::Player(player_projectile.weapon),
+                kind: DamageKind::Projectile,
+            });
+            projectile_hits.send(ProjectileHit {
+                projectile: id,
+                enemy,
+                position: start.lerp(end, along),
+                spent: spent && index + 1 == hit_count,
+            });
+        }
+        // Left for `despawn_spent_projectiles`, so modifiers get a chance to keep it going.
+        if spent {
+            commands.entity(id).insert(Spent);
         }
     }
 }
 
-fn resolve_enemy_projectiles(
+fn splash_on_hit(
+    mut commands: Commands,
+    mut projectile_hits: EventReader<ProjectileHit>,
+    projectiles: Query<(&PlayerProjectile, &SplashProjectile)>,
+) {
+    for hit in projectile_hits.read() {
+        let Ok((player_projectile, splash)) = projectiles.get(hit.projectile) else { continue };
+        spawn_splash(&mut commands, player_projectile.weapon, *splash, hit.position);
+    }
+}
+
+/// Leaves a splash for `do_splashes` to go off, and a pool behind it if it lingers.
+fn spawn_splash(commands: &mut Commands, weapon: PlayerWeapon, splash: SplashProjectile, position: Vec2) {
+    commands.spawn((
+        StateScoped(GameScreen::Running),
+        SplashProjectileResidual {
+            damage: splash.damage,
+            radius: splash.range,
+            weapon,
+        },
+        Transform::from_translation(position.extend(0.0)),
+    ));
+    if let Some(linger) = splash.linger {
+        commands.spawn((
+            StateScoped(GameScreen::Running),
+            LingeringPool {
+                damage: (splash.damage as f32 * linger.damage_fraction) as usize,
+                radius: splash.range,
+                weapon,
+                time_left: linger.duration,
+                pulse: Timer::from_seconds(POOL_PULSE_INTERVAL, TimerMode::Repeating),
+            },
+            circle_shape(splash.range, position),
+            Fill::color(PURPLE.with_alpha(0.15)),
+        ));
+    }
+}
+
+/// Every so often, each pool splashes everything in it again.
+fn pulse_lingering_pools(
+    time: Res<Time>,
+    mut commands: Commands,
+    mut pools: Query<(Entity, &mut LingeringPool, &Transform)>,
+) {
+    for (id, mut pool, transform) in pools.iter_mut() {
+        pool.time_left -= time.delta_seconds();
+        if pool.time_left <= 0.0 {
+            commands.entity(id).despawn();
+            continue;
+        }
+
+        pool.pulse.tick(time.delta());
+        if pool.pulse.just_finished() {
+            commands.spawn((
+                StateScoped(GameScreen::Running),
+                SplashProjectileResidual {
+                    damage: pool.damage,
+                    radius: pool.radius,
+                    weapon: pool.weapon,
+                },
+                Transform::from_translation(transform.position().extend(0.0)),
+            ));
+        }
+    }
+}
+
+/// Breaks smaller shards off projectiles that split, which carry on past whatever was hit.
+fn split_on_hit(
+    mut commands: Commands,
+    mut projectile_hits: EventReader<ProjectileHit>,
+    projectiles: Query<(&PlayerProjectile, &Projectile, &SplitOnHit)>,
+) {
+    for hit in projectile_hits.read() {
+        let Ok((player_projectile, projectile, split)) = projectiles.get(hit.projectile) else { continue };
+        let damage = (projectile.damage as f32 * split.damage_fraction) as usize;
+        let radius = projectile.radius * 0.6;
+        let aim = projectile.velocity.normalize_or_zero();
+        for direction in fan_directions(aim, split.count, split.spread) {
+            commands.spawn((
+                StateScoped(GameScreen::Running),
+                *player_projectile,
+                Projectile {
+                    damage,
+                    velocity: direction * projectile.velocity.length(),
+                    radius,
+                    pierce_left: 0,
+                    already_hit: vec![hit.enemy],
+                },
+                circle_shape(radius, hit.position),
+                Fill::color(YELLOW_GREEN),
+                Stroke::new(BLACK, 2f32),
+            ));
+        }
+    }
+}
+
+/// Arcs lightning from each enemy a chaining projectile hits on to the enemies nearest to it.
+fn chain_on_hit(
     mut commands: Commands,
-    mut player: Query<(&Position, &mut Health), With<Player>>,
-    mut query: Query<(Entity, &mut Projectile), With<EnemyProjectile>>,
+    mut projectile_hits: EventReader<ProjectileHit>,
+    mut damage_events: EventWriter<DamageEvent>,
+    spatial_index: Res<spatial::SpatialIndex>,
+    projectiles: Query<(&PlayerProjectile, &Projectile, &ChainLightning)>,
 ) {
-    let mut player = player.single_mut();
-    let (player_loc, mut player_hp) = (to_vec2!(player.0), player.1);
+    for hit in projectile_hits.read() {
+        let Ok((player_projectile, projectile, chain)) = projectiles.get(hit.projectile) else { continue };
+        let damage = (projectile.damage as f32 * chain.damage_fraction) as usize;
+        let mut struck = vec![hit.enemy];
+        let mut from = hit.position;
+        for _ in 0..chain.arcs {
+            let Some(next) = nearest_enemy(&spatial_index.enemies, from, chain.range, &struck) else { break };
+            damage_events.send(DamageEvent {
+                target: next.entity,
+                amount: damage,
+                source: DamageSource::Player(player_projectile.weapon),
+                kind: DamageKind::Chain,
+            });
+            commands.spawn((
+                StateScoped(GameScreen::Running),
+                SplashResidue(Timer::from_seconds(0.15, TimerMode::Once)),
+                ShapeBundle {
+                    path: GeometryBuilder::build_as(&shapes::Line(from, next.position)),
+                    spatial: SpatialBundle::from_transform(Transform::from_xyz(0.0, 0.0, 5.0)),
+                    ..default()
+                },
+                Stroke::new(SKY_300, 3.0),
+            ));
+            struck.push(next.entity);
+            from = next.position;
+        }
+    }
+}
+
+/// Sends used up projectiles that can bounce on to the nearest enemy they haven't hit yet.
+fn ricochet_on_hit(
+    mut commands: Commands,
+    mut projectile_hits: EventReader<ProjectileHit>,
+    spatial_index: Res<spatial::SpatialIndex>,
+    mut projectiles: Query<(&mut Ricochet, &mut Projectile, &mut Transform)>,
+) {
+    for hit in projectile_hits.read().filter(|hit| hit.spent) {
+        let Ok((mut ricochet, mut projectile, mut transform)) = projectiles.get_mut(hit.projectile) else { continue };
+        if ricochet.bounces == 0 {
+            continue;
+        }
+        let Some(next) = nearest_enemy(&spatial_index.enemies, hit.position, ricochet.range, &projectile.already_hit)
+        else {
+            continue;
+        };
 
-    for (id, mut projectile) in query.iter_mut() {
-        let approx_x = projectile.location.x as isize;
-        let approx_y = projectile.location.y as isize;
+        ricochet.bounces -= 1;
+        projectile.velocity = (next.position - hit.position).normalize_or_zero() * projectile.velocity.length();
+        transform.set_position(hit.position);
+        commands.entity(hit.projectile).remove::<Spent>();
+    }
+}
 
-        if (approx_x - player_loc.x as isize).abs() > 3000
-            || (approx_y - player_loc.y as isize).abs() > 3000 {
+fn expire_projectiles(
+    time: Res<Time>,
+    mut commands: Commands,
+    mut projectiles: Query<(Entity, &mut Lifetime)>,
+) {
+    for (id, mut lifetime) in projectiles.iter_mut() {
+        lifetime.0 -= time.delta_seconds();
+        if lifetime.0 <= 0.0 {
             commands.entity(id).despawn();
+        }
+    }
+}
+
+/// Points each blade at where it should be on its circle by the end of the tick, so it sweeps the arc in between.
+/// Every time a blade comes back round, it can hit everything again.
+fn orbit_blades(
+    time: Res<Time>,
+    player: Query<&Transform, With<Player>>,
+    mut blades: Query<(&mut OrbitingBlade, &mut Projectile, &Transform), Without<Player>>,
+) {
+    let player_pos = player.single().position();
+    let dt = time.delta_seconds();
+    let full_turn = std::f32::consts::TAU;
+    for (mut blade, mut projectile, transform) in blades.iter_mut() {
+        let turns = (blade.angle / full_turn).floor();
+        blade.angle += blade.spin * dt;
+        if (blade.angle / full_turn).floor() != turns {
+            projectile.already_hit.clear();
+        }
+
+        let next = player_pos + Vec2::from_angle(blade.angle) * blade.distance;
+        projectile.velocity = (next - transform.position()) / dt;
+    }
+}
+
+fn despawn_spent_projectiles(mut commands: Commands, projectiles: Query<Entity, With<Spent>>) {
+    for id in projectiles.iter() {
+        commands.entity(id).despawn();
+    }
+}
+
+/// Turns seeking projectiles towards the nearest enemy in range that they haven't hit yet.
+fn steer_seeking_projectiles(
+    time: Res<Time>,
+    spatial_index: Res<spatial::SpatialIndex>,
+    mut projectiles: Query<(&SeeksEnemies, &mut Projectile, &Transform)>,
+) {
+    let dt = time.delta_seconds();
+    for (seeking, mut projectile, transform) in projectiles.iter_mut() {
+        let position = transform.position();
+        let Some(target) = nearest_enemy(&spatial_index.enemies, position, seeking.range, &projectile.already_hit)
+        else {
             continue;
+        };
+
+        let turn = projectile.velocity.angle_between(target.position - position);
+        if turn.is_finite() {
+            let max_turn = seeking.turn_rate * dt;
+            projectile.velocity = Vec2::from_angle(turn.clamp(-max_turn, max_turn)).rotate(projectile.velocity);
         }
+    }
+}
 
-        const COLLIDE_DISTANCE: isize = PLAYER_RADIUS as isize + BASE_PROJECTILE_RADIUS as isize;
+/// Turns boomerangs around once they've flown for long enough, and catches them when they get back.
+/// On the way back they can hit everything again.
+fn return_boomerangs(
+    time: Res<Time>,
+    mut commands: Commands,
+    player: Query<&Transform, With<Player>>,
+    mut projectiles: Query<(Entity, &mut Boomerang, &mut Projectile, &Transform), Without<Player>>,
+) {
+    let player_pos = player.single().position();
+    for (id, mut boomerang, mut projectile, transform) in projectiles.iter_mut() {
+        if !boomerang.returning {
+            boomerang.return_after -= time.delta_seconds();
+            if boomerang.return_after > 0.0 {
+                continue;
+            }
+            boomerang.returning = true;
+            projectile.already_hit.clear();
+        }
 
-        if COLLIDE_DISTANCE > projectile.location.distance(player_loc) as isize {
-            player_hp.damage(projectile.damage);
+        let to_player = player_pos - transform.position();
+        if to_player.length() <= PLAYER_RADIUS {
             commands.entity(id).despawn();
+            continue;
+        }
+        projectile.velocity = to_player.normalize() * projectile.velocity.length();
+    }
+}
+
+/// The closest enemy to `position`, no further than `range` away, that isn't in `skip`.
+fn nearest_enemy(
+    enemies: &spatial::SpatialGrid,
+    position: Vec2,
+    range: f32,
+    skip: &[Entity],
+) -> Option<spatial::GridEntry> {
+    enemies.candidates(position, range).into_iter()
+        .filter(|enemy| !skip.contains(&enemy.entity) && enemy.position.distance(position) <= range)
+        .min_by(|a, b| a.position.distance_squared(position).total_cmp(&b.position.distance_squared(position)))
+}
+
+/// Works out which of the enemies a projectile reaches it actually hits, in order.
+/// `reached` is how far along its path the projectile reaches each enemy.
+/// Enemies are hit nearest first (ties broken by entity, so the order never depends on query order),
+/// skipping any it has already hit, until its pierce runs out.
+/// Returns the hits and whether the projectile is used up.
+fn pierce_through(
+    projectile: &mut Projectile,
+    reached: impl IntoIterator<Item = (f32, Entity)>,
+) -> (Vec<(f32, Entity)>, bool) {
+    let mut reached = reached.into_iter()
+        .filter(|(_, enemy)| !projectile.already_hit.contains(enemy))
+        .collect::<Vec<_>>();
+    reached.sort_by(|(a, a_enemy), (b, b_enemy)| a.total_cmp(b).then(a_enemy.cmp(b_enemy)));
+
+    let mut hits = Vec::new();
+    for (along, enemy) in reached {
+        hits.push((along, enemy));
+        projectile.already_hit.push(enemy);
+
+        if projectile.pierce_left == 0 {
+            return (hits, true);
+        }
+        projectile.pierce_left -= 1;
+    }
+    (hits, false)
+}
+
+/// Like player projectiles, these are checked along the path they're about to take this tick.
+/// Ones that miss are cleaned up by `move_projectiles` once they're far enough away.
+fn resolve_enemy_projectiles(
+    time: Res<Time>,
+    mut commands: Commands,
+    spatial_index: Res<spatial::SpatialIndex>,
+    mut damage_events: EventWriter<DamageEvent>,
+    player: Query<(Entity, &Transform), With<Player>>,
+    query: Query<(&Projectile, &Transform), With<EnemyProjectile>>,
+) {
+    let (player_id, player_transform) = player.single();
+    let player_loc = player_transform.position();
+    let dt = time.delta_seconds();
+
+    for candidate in spatial_index.enemy_projectiles.candidates(player_loc, PLAYER_RADIUS) {
+        let Ok((projectile, transform)) = query.get(candidate.entity) else { continue };
+        let start = transform.position();
+        let end = start + projectile.velocity * dt;
+
+        if spatial::sweep_circle(start, end, player_loc, PLAYER_RADIUS + projectile.radius).is_some() {
+            damage_events.send(DamageEvent {
+                target: player_id,
+                amount: projectile.damage,
+                source: DamageSource::Enemy,
+                kind: DamageKind::Projectile,
+            });
+            commands.entity(candidate.entity).despawn();
         }
     }
 }
@@ -688,14 +2204,21 @@ fn resolve_enemy_projectiles(
 fn create_power_ups(
     mut commands: Commands,
     mut power_up_spawn_timer: ResMut<PowerUpSpawnTimer>,
+    mut rng: ResMut<GameRng>,
+    balance: Res<balance::Balance>,
     time: Res<Time>,
-    player_pos: Query<&Position, With<Player>>
+    player_pos: Query<&Transform, With<Player>>
 ) {
     use num_traits::float::FloatConst;
 
-    const PROJECTILE_DIST_MIN: f32 = 300f32;
-    const PROJECTILE_DIST_MAX: f32 = 800f32;
-    let projectile_dist_diff = PROJECTILE_DIST_MAX - PROJECTILE_DIST_MIN;
+    let power_up_balance = balance.power_ups;
+    let projectile_dist_diff = power_up_balance.spawn_distance_max - power_up_balance.spawn_distance_min;
+
+    // The interval can change while a run is going when the balance is reloaded.
+    let spawn_interval = std::time::Duration::from_secs_f32(power_up_balance.spawn_interval);
+    if power_up_spawn_timer.0.duration() != spawn_interval {
+        power_up_spawn_timer.0.set_duration(spawn_interval);
+    }
 
     power_up_spawn_timer.0.tick(time.delta());
     if !power_up_spawn_timer.0.just_finished() {
@@ -703,22 +2226,21 @@ fn create_power_ups(
     }
     power_up_spawn_timer.0.reset();
 
-    let player_pos = player_pos.single();
-    let distance = (random::<f32>() * projectile_dist_diff) + PROJECTILE_DIST_MIN;
-    let angle_radians = random::<f32>() * f32::PI() * 2.0;
-    let pos = Position::new(
+    let player_pos = player_pos.single().position();
+    let distance = (rng.0.gen::<f32>() * projectile_dist_diff) + power_up_balance.spawn_distance_min;
+    let angle_radians = rng.0.gen::<f32>() * f32::PI() * 2.0;
+    let pos = Vec2::new(
         player_pos.x + (angle_radians.cos() * distance),
         player_pos.y + (angle_radians.sin() * distance),
     );
 
-    let power_up_type = PowerUpType::random();
+    let power_up_type = PowerUpType::random(&mut rng.0);
 
     commands.spawn((
-        RunningObject,
+        StateScoped(GameScreen::Running),
         PowerUp { time_since_created: 0.0 },
         power_up_type,
-        pos,
-        circle!(15.0, pos),
+        circle_shape(POWER_UP_RADIUS, pos),
         Fill::color(power_up_type.color()),
         Stroke::new(power_up_type.color().with_luminance(0.3), 2.0),
     ));
@@ -726,33 +2248,37 @@ fn create_power_ups(
 
 fn power_up_radius(time_since_created: f32) -> f32 {
     use std::ops::Mul;
-    time_since_created.mul(5.0).sin().mul(3.0) + 15.0
+    time_since_created.mul(5.0).sin().mul(POWER_UP_PULSE) + POWER_UP_RADIUS
 }
 
-fn update_power_ups(time: Res<Time>, mut query: Query<(&mut PowerUp, &Position, &mut Path)>) {
+fn update_power_ups(time: Res<Time>, mut query: Query<&mut PowerUp>) {
     let dt = time.delta_seconds();
-    for (mut power_up, pos, mut path) in query.iter_mut() {
+    for mut power_up in query.iter_mut() {
         power_up.time_since_created += dt;
-        *path = circle!(power_up_radius(power_up.time_since_created), pos).path;
     }
 }
 
 fn collect_power_ups(
     mut commands: Commands,
-    mut player: Query<(&Position, &mut PlayerState, &mut Health), With<Player>>,
-    power_ups: Query<(Entity, &Position, &PowerUp, &PowerUpType), With<PowerUp>>
+    balance: Res<balance::Balance>,
+    spatial_index: Res<spatial::SpatialIndex>,
+    mut player: Query<(&Transform, &Passives, &mut PlayerState, &mut Health), With<Player>>,
+    power_ups: Query<(&PowerUp, &PowerUpType)>
 ) {
-    let (player_pos, mut player_state, mut player_health) = player.single_mut();
-    let player_pos = to_vec2!(player_pos);
-    for (id, pos, power_up, power_up_type) in power_ups.iter() {
+    let (player_transform, passives, mut player_state, mut player_health) = player.single_mut();
+    let player_pos = player_transform.position();
+    let reach = PLAYER_RADIUS + passives.pickup_range;
+    for candidate in spatial_index.power_ups.candidates(player_pos, reach) {
+        let id = candidate.entity;
+        let Ok((power_up, power_up_type)) = power_ups.get(id) else { continue };
         let power_up_radius  = power_up_radius(power_up.time_since_created);
-        if to_vec2!(pos).distance(player_pos) < PLAYER_RADIUS + power_up_radius {
+        if candidate.position.distance(player_pos) < reach + power_up_radius {
             match power_up_type {
                 PowerUpType::Heal => {
-                    let heal_amount = player_health.max_hp / 5;
+                    let heal_amount = player_health.max_hp / balance.power_ups.heal_divisor;
                     player_health.heal(heal_amount);
                 },
-                PowerUpType::Boost => player_state.boost_time_left += 3.0,
+                PowerUpType::Boost => player_state.boost_time_left += balance.power_ups.boost_duration,
             };
             commands.entity(id).despawn();
         }
@@ -761,110 +2287,244 @@ fn collect_power_ups(
 
 fn player_ranged_attack(
     mut commands: Commands,
-    window: Query<&Window>,
-    camera: Query<(&Camera, &GlobalTransform)>,
-    query: Query<(Entity, &PlayerStats, &PlayerState, &Position), With<Player>>
+    mut damage_events: EventWriter<DamageEvent>,
+    player_input: Res<PlayerInput>,
+    balance: Res<balance::Balance>,
+    spatial_index: Res<spatial::SpatialIndex>,
+    query: Query<(&PlayerStats, &PlayerState, &Transform), With<Player>>
 ) {
-    let (player_id, stats, state, pos) = query.single();
+    if !player_input.firing {
+        return;
+    }
 
-    let (camera, transform) = camera.single();
-    let Some(relative_mouse_coords): Option<Vec2> = window.single()
-        .cursor_position()
-        .and_then(|mouse| camera.viewport_to_world_2d(transform, mouse))
-        .map(|mouse| mouse - Vec2::new(pos.x, pos.y))
+    let (stats, state, transform) = query.single();
+    let location = transform.position();
+
+    let Some(relative_mouse_coords): Option<Vec2> = player_input.aim
+        .map(|mouse| mouse - location)
     else { return };
 
     if !state.ranged_attack_timer.just_finished() {
         return;
     }
 
-    let damage = state.current_weapon.adjusted_damage(stats.ranged_attack_damage);
-    let velocity = {
-        let length = relative_mouse_coords.length();
-        let x = relative_mouse_coords.x / length;
-        let y = relative_mouse_coords.y / length;
-        let scale = stats.ranged_attack_speed;
-
-        Vec2::new(x * scale, y * scale)
-            * state.current_weapon.speed_multiplier()
-    };
-    let location = pos.into();
-
-    match state.current_weapon {
-        PlayerWeapon::Normal => {
-            let pierce_left = stats.ranged_attack_pierce;
-            let radius = BASE_PROJECTILE_RADIUS;
-            commands.spawn((
-                RunningObject,
-                PlayerProjectile,
-                Projectile { damage, velocity, location, radius, pierce_left, last_entity_hit: player_id },
-                circle!(radius, pos),
-                Fill::color(YELLOW_GREEN),
-                Stroke::new(BLACK, 5f32),
-            ));
+    let weapon = state.current_weapon;
+    let definition = &state.weapon_definition(weapon, &balance.weapons);
+    let damage = definition.damage(stats.ranged_attack_damage);
+    let pierce = definition.pierce(stats.ranged_attack_pierce);
+    let aim = relative_mouse_coords.normalize_or_zero();
+    let velocity = aim * stats.ranged_attack_speed * definition.speed_multiplier;
+
+    match definition.firing {
+        balance::WeaponFiring::Projectiles { count, spread } => {
+            for direction in fan_directions(velocity, count, spread) {
+                let projectile = Projectile {
+                    damage,
+                    velocity: direction,
+                    radius: definition.radius,
+                    pierce_left: pierce,
+                    already_hit: Vec::new(),
+                };
+                spawn_player_projectile(&mut commands, weapon, definition, stats, projectile, location);
+            }
         },
-        PlayerWeapon::Burst => {
-            for burst_info in distribute_burst(stats.ranged_attack_damage, stats.ranged_attack_pierce) {
+        balance::WeaponFiring::Burst { extra_pellets, spread_multiplier } => {
+            let bursts = distribute_burst(
+                damage,
+                pierce,
+                extra_pellets,
+                spread_multiplier,
+                &balance.weapons,
+            );
+            for burst_info in bursts {
+                let projectile = Projectile {
+                    damage: burst_info.damage,
+                    velocity: velocity.rotate(Vec2::from_angle(burst_info.offset_rad)),
+                    radius: definition.radius,
+                    pierce_left: burst_info.pierce,
+                    already_hit: Vec::new(),
+                };
                 let location = velocity * burst_info.offset_dist + location;
-                let radius = BASE_PROJECTILE_RADIUS * 0.5;
-                commands.spawn((
-                    RunningObject,
-                    PlayerProjectile,
-                    Projectile {
-                        damage: burst_info.damage,
-                        velocity: velocity.rotate(Vec2::from_angle(burst_info.offset_rad)),
-                        location,
-                        radius,
-                        pierce_left: burst_info.pierce,
-                        last_entity_hit: player_id,
-                    },
-                    circle!(radius, location),
-                    Fill::color(GREEN_YELLOW),
-                    Stroke::new(BLACK, 3f32),
-                ));
+                spawn_player_projectile(&mut commands, weapon, definition, stats, projectile, location);
             }
         },
-        PlayerWeapon::Splash => {
-            let radius = BASE_PROJECTILE_RADIUS * 1.7;
+        balance::WeaponFiring::Beam { length, width } => {
+            let end = location + aim * length;
+            let half_width = width / 2.0;
+            let reached = spatial_index.enemies.candidates_along(location, end, half_width).into_iter()
+                .filter_map(|enemy| {
+                    spatial::sweep_circle(location, end, enemy.position, enemy.radius + half_width)
+                        .map(|along| (along, enemy.entity))
+                });
+            // Beams don't stick around, so this projectile is only there to count down the pierce.
+            let mut beam = Projectile {
+                damage,
+                velocity: Vec2::ZERO,
+                radius: half_width,
+                pierce_left: pierce,
+                already_hit: Vec::new(),
+            };
+            let (hits, _) = pierce_through(&mut beam, reached);
+            for (along, enemy) in hits {
+                damage_events.send(DamageEvent {
+                    target: enemy,
+                    amount: damage,
+                    source: DamageSource::Player(weapon),
+                    kind: DamageKind::Projectile,
+                });
+                if let Some(splash) = definition.splash {
+                    let splash = SplashProjectile::new(splash, stats.ranged_attack_damage);
+                    spawn_splash(&mut commands, weapon, splash, location.lerp(end, along));
+                }
+            }
             commands.spawn((
-                RunningObject,
-                PlayerProjectile,
-                Projectile {
-                    damage, velocity, location, radius,
-                    pierce_left: 0,
-                    last_entity_hit: player_id,
-                },
-                SplashProjectile {
-                    damage: state.current_weapon.adjusted_splash_damage(stats.ranged_attack_damage),
-                    range: 100.0,
+                StateScoped(GameScreen::Running),
+                SplashResidue(Timer::from_seconds(0.15, TimerMode::Once)),
+                ShapeBundle {
+                    path: GeometryBuilder::build_as(&shapes::Line(location, end)),
+                    spatial: SpatialBundle::from_transform(Transform::from_xyz(0.0, 0.0, 5.0)),
+                    ..default()
                 },
-                circle!(radius, location),
-                Fill::color(PURPLE_800),
-                Stroke::new(BLACK, 7f32),
+                Stroke::new(definition.color(), width),
             ));
         },
+        balance::WeaponFiring::Orbit { count, distance, spin } => {
+            let full_turn = std::f32::consts::TAU;
+            for direction in fan_directions(aim, count, full_turn - full_turn / count as f32) {
+                let projectile = Projectile {
+                    damage,
+                    velocity: Vec2::ZERO,
+                    radius: definition.radius,
+                    pierce_left: usize::MAX,
+                    already_hit: Vec::new(),
+                };
+                let location = location + direction * distance;
+                let blade = spawn_player_projectile(&mut commands, weapon, definition, stats, projectile, location);
+                commands.entity(blade).insert(OrbitingBlade { angle: direction.y.atan2(direction.x), distance, spin });
+            }
+        },
+    }
+}
+
+/// Spawns one of the player's projectiles with everything its weapon adds to it.
+fn spawn_player_projectile(
+    commands: &mut Commands,
+    weapon: PlayerWeapon,
+    definition: &balance::WeaponDefinition,
+    stats: &PlayerStats,
+    projectile: Projectile,
+    location: Vec2,
+) -> Entity {
+    let radius = projectile.radius;
+    let mut projectile = commands.spawn((
+        StateScoped(GameScreen::Running),
+        PlayerProjectile { weapon },
+        projectile,
+        circle_shape(radius, location),
+        Fill::color(definition.color()),
+        Stroke::new(BLACK, (radius / 3.0).max(2.0)),
+    ));
+    if let Some(lifetime) = definition.lifetime {
+        projectile.insert(Lifetime(lifetime));
+    }
+    if let Some(splash) = definition.splash {
+        projectile.insert(SplashProjectile::new(splash, stats.ranged_attack_damage));
+    }
+    let id = projectile.id();
+    add_projectile_modifiers(projectile, &definition.modifiers);
+    id
+}
+
+/// Hits every enemy within reach of the player and knocks them away.
+fn player_close_attack(
+    mut commands: Commands,
+    mut player_input: ResMut<PlayerInput>,
+    mut damage_events: EventWriter<DamageEvent>,
+    spatial_index: Res<spatial::SpatialIndex>,
+    mut player: Query<(&PlayerStats, &Passives, &mut PlayerState, &Transform), With<Player>>,
+    mut enemies: Query<(&EnemyStats, &mut EnemyState), With<Enemy>>,
+) {
+    let requested = std::mem::take(&mut player_input.close_attack);
+    let (stats, passives, mut state, transform) = player.single_mut();
+    if !state.close_attack_timer.finished() {
+        return;
+    }
+
+    let player_pos = transform.position();
+    let radius = stats.close_attack_radius;
+    // The hair trigger card sets it off by itself as soon as anything comes into range.
+    let enemy_in_range = || spatial_index.enemies.candidates(player_pos, radius).into_iter()
+        .any(|enemy| enemy.position.distance(player_pos) <= enemy.radius + radius);
+    if !requested && !(passives.auto_shockwave && enemy_in_range()) {
+        return;
+    }
+    state.close_attack_timer.reset();
+
+    for enemy in spatial_index.enemies.candidates(player_pos, radius) {
+        if enemy.position.distance(player_pos) > enemy.radius + radius {
+            continue;
+        }
+        damage_events.send(DamageEvent {
+            target: enemy.entity,
+            amount: stats.close_attack_damage,
+            source: DamageSource::CloseAttack,
+            kind: DamageKind::Melee,
+        });
+        if let Ok((enemy_stats, mut enemy_state)) = enemies.get_mut(enemy.entity) {
+            // Bigger enemies are harder to push around.
+            let push = stats.close_attack_knockback * ENEMY_RADIUS / enemy_stats.radius;
+            enemy_state.knockback_velocity += (enemy.position - player_pos).normalize_or_zero() * push;
+        }
+    }
+
+    commands.spawn((
+        StateScoped(GameScreen::Running),
+        SplashResidue(Timer::from_seconds(0.25, TimerMode::Once)),
+        circle_shape(radius, player_pos),
+        Fill::color(GREEN_300.with_alpha(0.2)),
+        Stroke::new(GREEN_800.with_alpha(0.6), 4.0),
+    ));
+}
+
+fn add_projectile_modifiers(
+    mut projectile: bevy::ecs::system::EntityCommands,
+    modifiers: &balance::ProjectileModifiers,
+) {
+    if let Some(seeking) = modifiers.seeking {
+        projectile.insert(seeking);
+    }
+    if let Some(ricochet) = modifiers.ricochet {
+        projectile.insert(ricochet);
+    }
+    if let Some(chain) = modifiers.chain {
+        projectile.insert(chain);
+    }
+    if let Some(boomerang) = modifiers.boomerang {
+        projectile.insert(boomerang);
+    }
+    if let Some(split) = modifiers.split {
+        projectile.insert(split);
     }
 }
 
-fn check_and_resolve_player_death(mut commands: Commands, query: Query<&Health, With<Player>>) {
+fn check_and_resolve_player_death(mut next_screen: ResMut<NextState<GameScreen>>, query: Query<&Health, With<Player>>) {
     let player_hp = query.single().current_hp;
     if player_hp == 0 {
-        commands.remove_resource::<Running>();
-        commands.insert_resource(Transition::new(Running, LoseScreen));
+        next_screen.set(GameScreen::LoseScreen);
     }
 }
 
 fn move_projectiles(
     time: Res<Time>,
     mut commands: Commands,
-    mut query: Query<(Entity, &mut Projectile, &mut Path)>,
-    player_loc: Query<&Position, With<Player>>,
+    mut query: Query<(Entity, &Projectile, &mut Transform), Without<Player>>,
+    player_loc: Query<&Transform, With<Player>>,
 ) {
-    let player_loc = player_loc.single();
+    let player_loc = player_loc.single().position();
 
-    for (id, mut proj, mut path) in query.iter_mut() {
-        let approx_x = proj.location.x as isize;
-        let approx_y = proj.location.y as isize;
+    for (id, proj, mut transform) in query.iter_mut() {
+        let approx_x = transform.translation.x as isize;
+        let approx_y = transform.translation.y as isize;
 
         if (approx_x - player_loc.x as isize).abs() > 2000
             || (approx_y - player_loc.y as isize).abs() > 1500 {
@@ -872,9 +2532,7 @@ fn move_projectiles(
             continue;
         }
 
-        let vel = proj.velocity;
-        proj.location += vel * time.delta_seconds();
-        *path = circle!(proj.radius, proj.location).path;
+        transform.translate_by(proj.velocity * time.delta_seconds());
     }
 }
 
@@ -896,198 +2554,451 @@ fn update_player_color(mut query: Query<(&mut Fill, &Health), With<Player>>) {
 }
 
 fn spawn_wave_if_no_enemies(
+    time: Res<Time>,
     mut commands: Commands,
     mut player_upgrade_counter: ResMut<PlayerUpgradeCounter>,
-    mut player_upgrade_counter_text: Query<&mut Text, (With<PlayerUpgradeCounterText>, Without<WaveCounterText>)>,
     mut wave_counter: ResMut<WaveCounter>,
-    mut wave_counter_text: Query<&mut Text, (With<WaveCounterText>, Without<PlayerUpgradeCounterText>)>,
-    mut player: Query<(&Position, &mut Health, &PlayerStats), With<Player>>,
-    query: Query<&Enemy>
+    mut intermission: ResMut<Intermission>,
+    mut pending_groups: ResMut<PendingGroups>,
+    mut rng: ResMut<GameRng>,
+    balance: Res<balance::Balance>,
+    mut player: Query<(&Transform, &mut Health, &PlayerStats), With<Player>>,
+    query: Query<&Enemy>,
+    markers: Query<&SpawnMarker>,
 ) {
-    use num_traits::float::FloatConst;
-
     // If the query returns Some(_), then there are still enemies left.
     // Therefore, a new wave should not be spawned.
-    if query.iter().len() != 0 {
+    // The same goes for enemies that are still on their way.
+    if query.iter().len() != 0 || markers.iter().len() != 0 || !pending_groups.0.is_empty() {
         return;
     }
 
+    let (player_transform, mut player_health, player_stats) = player.single_mut();
+    match intermission.0 {
+        // The wave was only just cleared, so it's time for the player's reward.
+        // Note: this also happens at the start of a run, before the first wave.
+        None => {
+            player_upgrade_counter.add_unused();
+            player_health.heal(player_stats.end_of_round_heal);
+            if wave_counter.0 > 0 && balance.waves.intermission > 0.0 {
+                intermission.0 = Some(balance.waves.intermission);
+                return;
+            }
+        },
+        Some(time_left) if time_left > time.delta_seconds() => {
+            intermission.0 = Some(time_left - time.delta_seconds());
+            return;
+        },
+        Some(_) => {},
+    }
+    intermission.0 = None;
+
     // Since we're spawning a new wave, increment the wave counter.
     // Note: WaveCounter::default() is 0 so the first wave will be 1.
     wave_counter.0 += 1;
-    let mut wave_counter_text = wave_counter_text.single_mut();
-    *wave_counter_text = Text::from_section(format!("Wave {}", wave_counter.0), TextStyle::default());
 
-    player_upgrade_counter.add_unused();
-    *player_upgrade_counter_text.single_mut() =
-        Text::from_section(player_upgrade_counter.display_text(), TextStyle::default());
+    let player_pos = player_transform.position();
+    let wave = wave_counter.0;
+    let Some(scripted) = balance.waves.scripted_wave(wave) else {
+        warn!("Nothing in the wave script covers wave {wave}");
+        return;
+    };
+    for group in &scripted.groups {
+        let count = group.count(wave - scripted.from_wave);
+        if group.delay > 0.0 {
+            pending_groups.0.push(PendingGroup { time_left: group.delay, group: *group, count, wave });
+        } else {
+            spawn_group(&mut commands, &mut pending_groups, group, count, player_pos, wave, &balance, &mut rng.0);
+        }
+    }
+}
 
-    let (player_pos, mut player_health, player_stats) = player.single_mut();
-    player_health.heal(player_stats.end_of_round_heal);
-
-    let enemies_to_spawn = 8 + (2 * wave_counter.0);
-    const ENEMY_DISTANCE_MIN: f32 = 400f32;
-    let enemy_distance_max: f32 = 800f32 + (20f32 * wave_counter.0 as f32);
-    let enemy_distance_diff = enemy_distance_max - ENEMY_DISTANCE_MIN;
-    for _ in 0..enemies_to_spawn {
-        let angle_radians: f32 = random::<f32>() * f32::PI() * 2.0;
-        let distance: f32 = ENEMY_DISTANCE_MIN + (random::<f32>() * enemy_distance_diff);
-        let pos = Position::new(
-            player_pos.x + (angle_radians.cos() * distance),
-            player_pos.y + (angle_radians.sin() * distance),
-        );
-        let stats = EnemyStats::new(
-            9 + wave_counter.0 as usize,
-            1.0 * f32::powi(0.95, (wave_counter.0 - 1) as i32),
-            if wave_counter.0 < 5
-                || (wave_counter.0 < 10 && random::<f32>() < 0.9)
-                || (wave_counter.0 < 25 && random::<f32>() < 0.8)
-                || (wave_counter.0 < 20 && random::<f32>() < 0.6)
-                || wave_counter.0 > 20
-            { 0 }
-            else {
-                (wave_counter.0 - 5) as usize
-                * f32::powi(1.05, (wave_counter.0 - 10) as i32) as usize
+/// Marks where a group from the wave script is about to spawn around the player.
+/// Trickling groups only mark their first enemy here, and leave the rest for later.
+fn spawn_group(
+    commands: &mut Commands,
+    pending_groups: &mut PendingGroups,
+    group: &balance::SpawnGroup,
+    count: usize,
+    player_pos: Vec2,
+    wave: isize,
+    balance: &balance::Balance,
+    rng: &mut impl Rng,
+) {
+    use balance::SpawnPattern;
+    use num_traits::float::FloatConst;
+
+    if let SpawnPattern::Trickle { interval } = group.pattern {
+        if count > 1 {
+            pending_groups.0.push(PendingGroup { time_left: interval, group: *group, count: count - 1, wave });
+        }
+    }
+
+    // Rolled once for the whole group, so its enemies can line up with each other.
+    let group_angle = match group.pattern {
+        SpawnPattern::Scatter | SpawnPattern::Trickle { .. } => 0.0,
+        _ => rng.gen::<f32>() * f32::PI() * 2.0,
+    };
+    let group_direction = Vec2::from_angle(group_angle);
+
+    let count = if let SpawnPattern::Trickle { .. } = group.pattern { count.min(1) } else { count };
+    for i in 0..count {
+        let pos = match group.pattern {
+            SpawnPattern::Scatter | SpawnPattern::Trickle { .. } => scatter_position(player_pos, wave, balance, rng),
+            SpawnPattern::Ring { distance } => {
+                let angle = group_angle + i as f32 / count as f32 * f32::PI() * 2.0;
+                player_pos + Vec2::from_angle(angle) * distance
             },
-            1.5,
-            200.0 * f32::powi(1.05, wave_counter.0 as i32),
-            75.0 * f32::powi(1.05, wave_counter.0 as i32),
-        );
+            SpawnPattern::Cluster { distance, spread } => {
+                let angle = rng.gen::<f32>() * f32::PI() * 2.0;
+                let offset = Vec2::from_angle(angle) * rng.gen::<f32>() * spread;
+                player_pos + group_direction * distance + offset
+            },
+            SpawnPattern::Line { distance, length } => {
+                let along = if count > 1 { i as f32 / (count - 1) as f32 - 0.5 } else { 0.0 };
+                player_pos + group_direction * distance + group_direction.perp() * along * length
+            },
+        };
+        let kind = roll_enemy_kind(group.kind, wave, balance, rng);
         commands.spawn((
-            RunningObject,
-            NoFrustumCulling, // prevent weird invisibility
-            Enemy,
-            pos,
-            Health::new(24 + (2*wave_counter.0 as usize)),
-            stats,
-            EnemyState::from_enemy_stats(stats).with_random_ranged_timer(),
-            circle!(ENEMY_RADIUS, pos),
-            Fill::color(ENEMY_COLOR),
-            Stroke::new(BLACK, 3.0),
+            StateScoped(GameScreen::Running),
+            SpawnMarker { time_left: balance.waves.spawn_telegraph, kind, wave, scaling: group.scaling },
+            circle_shape(balance.enemies.stats(kind).radius, pos),
+            Fill::color(kind.color().with_alpha(0.25)),
         ));
     }
 }
 
-fn apply_player_upgrade(stats: &mut PlayerStats, health: &mut Health, upgrade: PlayerUpgrade) {
-    match upgrade {
-        PlayerUpgrade::AttackUpgrade => {
-            stats.ranged_attack_damage += 2;
-            stats.ranged_attack_cooldown *= 0.95;
-            stats.ranged_attack_pierce += 1;
-            stats.close_attack_damage += 10;
+/// Somewhere random within the wave's spawn distances.
+fn scatter_position(player_pos: Vec2, wave: isize, balance: &balance::Balance, rng: &mut impl Rng) -> Vec2 {
+    use num_traits::float::FloatConst;
+
+    let waves = &balance.waves;
+    let enemy_distance_min = waves.spawn_distance_min;
+    let enemy_distance_diff = waves.spawn_distance_max(wave) - enemy_distance_min;
+    let angle_radians: f32 = rng.gen::<f32>() * f32::PI() * 2.0;
+    let distance: f32 = enemy_distance_min + (rng.gen::<f32>() * enemy_distance_diff);
+    Vec2::new(
+        player_pos.x + (angle_radians.cos() * distance),
+        player_pos.y + (angle_radians.sin() * distance),
+    )
+}
+
+fn roll_enemy_kind(
+    group_kind: balance::GroupKind,
+    wave: isize,
+    balance: &balance::Balance,
+    rng: &mut impl Rng,
+) -> EnemyKind {
+    match group_kind {
+        balance::GroupKind::Only(kind) => kind,
+        balance::GroupKind::Mixed if balance.waves.rolls_shooter(wave, rng) => EnemyKind::Shooter,
+        balance::GroupKind::Mixed => balance.enemies.pick_kind(wave, rng),
+    }
+}
+
+/// Starts spawning groups once they've waited their turn, around wherever the player is by then.
+fn spawn_pending_groups(
+    time: Res<Time>,
+    mut commands: Commands,
+    mut pending_groups: ResMut<PendingGroups>,
+    mut rng: ResMut<GameRng>,
+    balance: Res<balance::Balance>,
+    player: Query<&Transform, With<Player>>,
+) {
+    let player_pos = player.single().position();
+    let dt = time.delta_seconds();
+    for mut pending in std::mem::take(&mut pending_groups.0) {
+        pending.time_left -= dt;
+        if pending.time_left > 0.0 {
+            pending_groups.0.push(pending);
+            continue;
+        }
+        let PendingGroup { group, count, wave, .. } = pending;
+        spawn_group(&mut commands, &mut pending_groups, &group, count, player_pos, wave, &balance, &mut rng.0);
+    }
+}
+
+/// Turns spawn markers into enemies once they've been shown for long enough.
+fn resolve_spawn_markers(
+    time: Res<Time>,
+    mut commands: Commands,
+    mut rng: ResMut<GameRng>,
+    balance: Res<balance::Balance>,
+    mut markers: Query<(Entity, &mut SpawnMarker, &Transform)>,
+) {
+    let dt = time.delta_seconds();
+    for (id, mut marker, transform) in markers.iter_mut() {
+        marker.time_left -= dt;
+        if marker.time_left > 0.0 {
+            continue;
+        }
+        let pos = transform.position();
+        spawn_enemy(&mut commands, marker.kind, pos, marker.wave, marker.scaling, &balance, &mut rng.0);
+        commands.entity(id).despawn();
+    }
+}
+
+fn spawn_enemy(
+    commands: &mut Commands,
+    kind: EnemyKind,
+    pos: Vec2,
+    wave: isize,
+    scaling: balance::StatScaling,
+    balance: &balance::Balance,
+    rng: &mut impl Rng,
+) -> Entity {
+    let stats = EnemyStats::for_kind(kind, wave, balance).scaled(scaling);
+    let max_hp = ((balance.enemy_health(kind, wave) as f32 * scaling.health) as usize).max(1);
+    let id = commands.spawn((
+        StateScoped(GameScreen::Running),
+        Enemy,
+        kind,
+        Health::new(max_hp),
+        LastHitBy::default(),
+        stats,
+        EnemyState::from_enemy_stats(stats).with_random_ranged_timer(rng),
+        circle_shape(stats.radius, pos),
+        Fill::color(kind.color()),
+        Stroke::new(BLACK, 3.0),
+    )).id();
+
+    if kind == EnemyKind::Boss {
+        let ring_damage = balance.waves.melee_damage(wave) as f32
+            * balance.bosses.ring_damage_multiplier
+            * scaling.damage;
+        commands.entity(id).insert(Boss::new(&balance.bosses, ring_damage as usize));
+    }
+    id
+}
+
+fn apply_upgrade_card(
+    stats: &mut PlayerStats,
+    health: &mut Health,
+    passives: &mut Passives,
+    state: &mut PlayerState,
+    effect: &balance::CardEffect,
+    upgrades: &balance::UpgradeBalance,
+) {
+    match effect {
+        balance::CardEffect::Attack => {
+            stats.ranged_attack_damage += upgrades.attack_ranged_damage;
+            stats.ranged_attack_cooldown *= upgrades.attack_ranged_cooldown_multiplier;
+            stats.close_attack_damage += upgrades.attack_close_damage;
         },
-        PlayerUpgrade::HealthUpgrade => {
-            health.add_max_hp(20);
-            health.heal(health.max_health() / 5);
-            stats.end_of_round_heal += 4;
+        balance::CardEffect::Health => {
+            health.add_max_hp(upgrades.health_max_hp);
+            health.heal(health.max_health() / upgrades.health_heal_divisor);
+            stats.end_of_round_heal += upgrades.health_end_of_round_heal;
         },
-        PlayerUpgrade::SpeedUpgrade => {
-            stats.movement_speed *= 1.15;
-            stats.ranged_attack_speed *= 1.2;
+        balance::CardEffect::Speed => {
+            stats.movement_speed *= upgrades.speed_movement_multiplier;
+            stats.ranged_attack_speed *= upgrades.speed_ranged_attack_speed_multiplier;
         },
-    }
-}
-
-fn distribute_burst(damage: usize, pierce: usize) -> Vec<BurstInfo> {
+        balance::CardEffect::MaxHealth(amount) => {
+            health.add_max_hp(*amount);
+            health.heal(*amount);
+        },
+        balance::CardEffect::CloseAttackDamage(amount) => stats.close_attack_damage += amount,
+        balance::CardEffect::EndOfRoundHeal(amount) => stats.end_of_round_heal += amount,
+        balance::CardEffect::Lifesteal(amount) => passives.heal_per_kill += amount,
+        balance::CardEffect::Regeneration(amount) => passives.regeneration += amount,
+        balance::CardEffect::Magnet(range) => passives.pickup_range += range,
+        balance::CardEffect::AutoShockwave => passives.auto_shockwave = true,
+        balance::CardEffect::Mutation(effects) => state.weapon_mutations.extend_from_slice(effects),
+    }
+}
+
+/// Every pellet gets the weapon's full pierce, so pierce upgrades help bursts as much as anything else.
+/// `extra_pellets` are added to however many pellets the burst would have,
+/// and the gaps between them are multiplied by `spread_multiplier`.
+fn distribute_burst(
+    damage: usize,
+    pierce: usize,
+    extra_pellets: usize,
+    spread_multiplier: f32,
+    weapons: &balance::WeaponBalance,
+) -> Vec<BurstInfo> {
     let total = damage * pierce;
+    let [small_spread, medium_spread, large_spread] = weapons.burst_spreads;
 
-    if total <= 200 {
-        let mut bursts = Vec::with_capacity(5);
-        for i in -2i8..=2 {
-            bursts.push(BurstInfo {
-                damage: (total + 3) / 6,
-                pierce: 2,
-                offset_rad: 0.15 * f32::from(i),
-                offset_dist: 0.0,
-            })
-        }
-        return bursts;
-    }
-
-    if total <= 600 {
-        let mut bursts = Vec::with_capacity(7);
-        for i in -3i8..=3 {
-            bursts.push(BurstInfo {
-                damage: damage * 2 / 3,
-                pierce: pierce / 4,
-                offset_rad: 0.1 * f32::from(i),
-                offset_dist: 0.0,
-            })
-        }
-        return bursts;
-    }
+    let (pellets, pellet_damage, spread) = if total <= weapons.small_burst_max_total {
+        (5, (total + 3) / 6, small_spread)
+    } else if total <= weapons.medium_burst_max_total {
+        (7, damage * 2 / 3, medium_spread)
+    } else {
+        (9, damage / 5, large_spread)
+    };
 
-    let mut bursts = Vec::with_capacity(9);
-    for i in -4i8..=4 {
-        bursts.push(BurstInfo {
-            damage: damage / 5,
-            pierce: pierce - 4,
-            offset_rad: 0.12 * f32::from(i),
+    let pellets = pellets + extra_pellets;
+    let middle = (pellets - 1) as f32 / 2.0;
+    (0..pellets)
+        .map(|i| BurstInfo {
+            damage: pellet_damage,
+            pierce,
+            offset_rad: spread * spread_multiplier * (i as f32 - middle),
             offset_dist: 0.0,
         })
-    }
+        .collect()
+}
+
+#[derive(States)]
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash, Default)]
+enum GameScreen {
+    #[default] MainMenu,
+    Guide,
+    Running,
+    LoseScreen,
+}
 
-    bursts
+/// Only exists while the game screen is `GameScreen::Running`,
+/// so pausing doesn't tear down anything scoped to the run.
+#[derive(SubStates)]
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash, Default)]
+#[source(GameScreen = GameScreen::Running)]
+enum RunState {
+    #[default] Playing,
+    Paused,
 }
 
 #[derive(Resource)]
+#[derive(Debug, Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Hash, Default)]
+struct WaveCounter(pub isize);
+#[derive(Component)]
 #[derive(Debug, Copy, Clone)]
-struct Running;
+struct WaveCounterText;
+
+/// What the player wants to do on the next gameplay tick.
+/// Filled in from the keyboard and mouse when playing normally,
+/// or by an `InputScript` in a headless simulation.
 #[derive(Resource)]
-#[derive(Debug, Copy, Clone)]
-struct Paused;
+#[derive(Debug, Copy, Clone, PartialEq, Default)]
+struct PlayerInput {
+    /// Direction to move in, with a length of at most 1.
+    pub movement: Vec2,
+    /// The point in the world being aimed at, if any.
+    pub aim: Option<Vec2>,
+    pub firing: bool,
+    pub cycle_weapon_left: bool,
+    pub cycle_weapon_right: bool,
+    pub upgrade: Option<PlayerUpgrade>,
+    /// Starts the next wave straight away, if it's being waited for.
+    pub call_wave: bool,
+    /// Uses the close attack, if it's ready.
+    pub close_attack: bool,
+}
+
+/// The only source of randomness gameplay is allowed to use,
+/// so that a run can be reproduced from its seed.
 #[derive(Resource)]
-#[derive(Debug, Copy, Clone)]
-struct MainMenu;
+#[derive(Debug, Clone)]
+struct GameRng(pub StdRng);
+
+/// The seed every run should use, if one was picked on the command line.
+/// Otherwise every run gets a fresh random seed.
 #[derive(Resource)]
-#[derive(Debug, Copy, Clone)]
-struct Guide;
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash, Default)]
+struct ChosenSeed(pub Option<u64>);
+
+/// The seed the current (or last) run was started with.
+#[derive(Resource)]
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash, Default)]
+struct RunSeed(pub u64);
+
+/// Statistics for the current run, folded into the save file when the run ends.
 #[derive(Resource)]
+#[derive(Debug, Clone, PartialEq, Default)]
+struct RunStats {
+    pub enemies_killed: u64,
+    /// Points for the enemies killed, which depend on their kind.
+    pub kill_score: u64,
+    /// Points for calling waves early.
+    pub bonus_score: u64,
+    pub damage_taken: u64,
+    pub damage_dealt: u64,
+    /// Indexed by `PlayerWeapon`, and only as long as the last weapon that killed anything.
+    pub kills_by_weapon: Vec<u64>,
+    pub close_attack_kills: u64,
+    pub play_time: f64,
+}
+
+#[derive(Component)]
 #[derive(Debug, Copy, Clone)]
-struct LoseScreen;
+struct PlayerHealthText;
 
 #[derive(Component)]
 #[derive(Debug, Copy, Clone)]
-struct MainMenuItem;
+struct ScoreText;
+
 #[derive(Component)]
 #[derive(Debug, Copy, Clone)]
-struct LoseScreenItem;
+struct CloseAttackText;
+
 #[derive(Component)]
 #[derive(Debug, Copy, Clone)]
-struct RunningObject;
+struct WeaponText;
+
 #[derive(Component)]
 #[derive(Debug, Copy, Clone)]
-struct GuideItem;
+struct UpgradeScreen;
 
-#[derive(Resource)]
-#[derive(Debug, Copy, Clone, Default)]
-struct Transition<From: Resource, To: Resource> {
-    _make_the_compiler_happy: std::marker::PhantomData<(From, To)>
-}
+/// Holds the cards on offer, only shown while there are some.
+#[derive(Component)]
+#[derive(Debug, Copy, Clone)]
+struct DraftPanel;
 
-#[derive(Resource)]
-#[derive(Debug, Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Hash, Default)]
-struct WaveCounter(pub isize);
+/// Which slot of the offer this card shows.
 #[derive(Component)]
 #[derive(Debug, Copy, Clone)]
-struct WaveCounterText;
+struct DraftCardButton(usize);
 
-#[derive(Resource)]
-#[derive(Debug, Copy, Clone, Default)]
-struct HighScore(pub isize);
+#[derive(Component)]
+#[derive(Debug, Copy, Clone)]
+struct IntermissionText;
 
 #[derive(Component)]
 #[derive(Debug, Copy, Clone)]
-struct PlayerHealthText;
+struct BossHealthBar;
+
+#[derive(Component)]
+#[derive(Debug, Copy, Clone)]
+struct BossHealthBarFill;
 
 #[derive(Resource)]
 #[derive(Debug, Copy, Clone, Default)]
 struct PlayerUpgradeCounter {
-    pub attack_upgrades: usize,
-    pub health_upgrades: usize,
-    pub speed_upgrades: usize,
+    pub cards_picked: usize,
+    pub weapon_upgrades: usize,
     pub unused_upgrades: usize,
 }
+
+/// The upgrade cards on offer, and what's been done with the draft so far this run.
+/// Cards are indices into `balance.draft.cards`.
+#[derive(Resource)]
+#[derive(Debug, Clone, Default)]
+struct Draft {
+    offer: Vec<usize>,
+    /// Never offered again this run.
+    banished: Vec<usize>,
+    /// Every card picked so far, in order.
+    picks: Vec<usize>,
+    rerolls_left: usize,
+    banishes_left: usize,
+}
+
+/// What the player gets from upgrade cards that doesn't fit in `PlayerStats`.
+#[derive(Component)]
+#[derive(Debug, Copy, Clone, Default)]
+struct Passives {
+    heal_per_kill: usize,
+    /// Health per second.
+    regeneration: f32,
+    regeneration_progress: f32,
+    /// How much further away than touching distance power ups get picked up from.
+    pickup_range: f32,
+    /// Whether the close attack goes off by itself when an enemy is in range.
+    auto_shockwave: bool,
+}
 #[derive(Component)]
 #[derive(Debug, Copy, Clone)]
 struct PlayerUpgradeCounterText;
@@ -1100,36 +3011,267 @@ struct Player;
 #[derive(Debug, Copy, Clone, Default)]
 struct Enemy;
 
+/// What an enemy does, on top of the stats its wave gives it.
+#[derive(Component, Serialize, Deserialize)]
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+enum EnemyKind {
+    /// Heads straight for the player.
+    Chaser,
+    /// Keeps its distance and shoots at the player.
+    Shooter,
+    /// Small and quick, and dashes at the player when it gets close.
+    Dasher,
+    /// Big, slow and hard to kill.
+    Brute,
+    /// Breaks into chasers when it dies.
+    Splitter,
+    /// Heals the enemies around it.
+    Healer,
+    /// Shows up on its own every few waves, with attacks that change as it gets hurt.
+    Boss,
+}
+
+/// The attacks only a boss has, on top of being an enemy.
+#[derive(Component)]
+#[derive(Debug, Clone)]
+struct Boss {
+    ring_timer: Timer,
+    summon_timer: Timer,
+    charge_timer: Timer,
+    rings_fired: usize,
+    ring_damage: usize,
+}
+
+/// Groups from the wave script that are waiting to start spawning,
+/// either because of their delay or because they trickle in.
+#[derive(Resource)]
+#[derive(Debug, Clone, Default)]
+struct PendingGroups(pub Vec<PendingGroup>);
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+struct PendingGroup {
+    pub time_left: f32,
+    pub group: balance::SpawnGroup,
+    /// How many enemies are left to spawn.
+    pub count: usize,
+    pub wave: isize,
+}
+
+/// Shows where an enemy is about to spawn, and spawns it once `time_left` runs out.
+#[derive(Component)]
+#[derive(Debug, Copy, Clone, PartialEq)]
+struct SpawnMarker {
+    pub time_left: f32,
+    pub kind: EnemyKind,
+    pub wave: isize,
+    pub scaling: balance::StatScaling,
+}
+
+/// Seconds until the next wave starts, while waiting between waves.
+#[derive(Resource)]
+#[derive(Debug, Copy, Clone, PartialEq, Default)]
+struct Intermission(pub Option<f32>);
+
 #[derive(Component)]
 #[derive(Debug, Copy, Clone)]
-struct PlayerProjectile;
+struct PlayerProjectile {
+    pub weapon: PlayerWeapon,
+}
+
+/// What the player last damaged an enemy with, which gets the credit if it dies.
+#[derive(Component)]
+#[derive(Debug, Copy, Clone, PartialEq, Default)]
+struct LastHitBy(pub Option<DamageSource>);
+
+/// All damage goes through these, so there's one place that applies it and reacts to it.
+#[derive(Event)]
+#[derive(Debug, Copy, Clone, PartialEq)]
+struct DamageEvent {
+    pub target: Entity,
+    pub amount: usize,
+    pub source: DamageSource,
+    pub kind: DamageKind,
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+enum DamageSource {
+    Player(PlayerWeapon),
+    /// The player's close attack.
+    CloseAttack,
+    Enemy,
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+enum DamageKind {
+    Melee,
+    Projectile,
+    Splash,
+    /// Lightning arcing between enemies.
+    Chain,
+}
+
+/// A number floating up from something that just took damage.
+#[derive(Component)]
+#[derive(Debug, Clone, PartialEq)]
+struct DamageNumber(pub Timer);
+
+/// Makes an enemy flash white for a moment after it's hit.
+#[derive(Component)]
+#[derive(Debug, Clone, PartialEq)]
+struct HitFlash(pub Timer);
+
+#[derive(Event)]
+#[derive(Debug, Copy, Clone, PartialEq)]
+struct EnemyKilled {
+    pub kind: EnemyKind,
+    pub position: Vec2,
+    /// `None` if it died without the player's help.
+    pub killer: Option<DamageSource>,
+}
 #[derive(Component)]
 #[derive(Debug, Copy, Clone)]
 struct EnemyProjectile;
 
+/// An enemy projectile that turns towards the player.
+#[derive(Component)]
+#[derive(Debug, Copy, Clone, PartialEq)]
+struct Homing {
+    /// In radians per second.
+    pub turn_rate: f32,
+    pub time_left: f32,
+}
+
+/// Left behind by enemies, and blows up once its fuse runs out.
+#[derive(Component)]
+#[derive(Debug, Clone, PartialEq)]
+struct Mine {
+    pub damage: usize,
+    pub radius: f32,
+    pub fuse: Timer,
+}
+
 #[derive(Component)]
 #[derive(Debug, Clone, PartialEq)]
 struct Projectile {
     pub damage: usize,
     pub velocity: Vec2,
-    pub location: Vec2,
     pub pierce_left: usize,
-    pub last_entity_hit: Entity,
+    /// Everything this projectile has already hit, so it never hits the same thing twice.
+    /// Pierce keeps this to a handful of entities, so a `Vec` is plenty.
+    pub already_hit: Vec<Entity>,
     pub radius: f32,
 }
 
+/// Sent for every enemy a player projectile hits, for its modifiers to react to.
+#[derive(Event)]
+#[derive(Debug, Copy, Clone, PartialEq)]
+struct ProjectileHit {
+    pub projectile: Entity,
+    pub enemy: Entity,
+    pub position: Vec2,
+    /// Whether this hit used up the last of the projectile's pierce.
+    pub spent: bool,
+}
+
+/// A player projectile that's been used up, and gets despawned at the end of the collision checks.
+#[derive(Component)]
+#[derive(Debug, Copy, Clone)]
+struct Spent;
+
+/// Seconds until a projectile disappears, whether it's hit anything or not.
+#[derive(Component)]
+#[derive(Debug, Copy, Clone, PartialEq)]
+struct Lifetime(pub f32);
+
+/// A player projectile that circles the player instead of flying off.
+#[derive(Component)]
+#[derive(Debug, Copy, Clone, PartialEq)]
+struct OrbitingBlade {
+    /// Radians, and keeps counting up past a full turn so each time round can be told apart.
+    pub angle: f32,
+    pub distance: f32,
+    /// Radians per second.
+    pub spin: f32,
+}
+
+/// Turns a player projectile towards the nearest enemy within `range`,
+/// by up to `turn_rate` radians a second.
+#[derive(Component, Serialize, Deserialize)]
+#[derive(Debug, Copy, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+struct SeeksEnemies {
+    pub turn_rate: f32,
+    pub range: f32,
+}
+
+/// Instead of being used up, a player projectile bounces on to the nearest enemy
+/// within `range` that it hasn't hit yet, up to `bounces` times.
+#[derive(Component, Serialize, Deserialize)]
+#[derive(Debug, Copy, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+struct Ricochet {
+    pub bounces: usize,
+    pub range: f32,
+}
+
+/// Every hit arcs on to up to `arcs` more enemies, each within `range` of the last,
+/// doing `damage_fraction` of the projectile's damage to each.
+#[derive(Component, Serialize, Deserialize)]
+#[derive(Debug, Copy, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+struct ChainLightning {
+    pub arcs: usize,
+    pub range: f32,
+    pub damage_fraction: f32,
+}
+
+/// Flies back to the player after `return_after` seconds.
+#[derive(Component, Serialize, Deserialize)]
+#[derive(Debug, Copy, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+struct Boomerang {
+    /// Counts down once it's been fired.
+    pub return_after: f32,
+    #[serde(skip)]
+    pub returning: bool,
+}
+
+/// Every hit breaks off `count` shards, spread over `spread` radians around the way it was going,
+/// each doing `damage_fraction` of the projectile's damage.
+#[derive(Component, Serialize, Deserialize)]
+#[derive(Debug, Copy, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+struct SplitOnHit {
+    pub count: usize,
+    pub spread: f32,
+    pub damage_fraction: f32,
+}
+
 #[derive(Component)]
 #[derive(Debug, Copy, Clone, PartialEq, Default)]
 struct SplashProjectile {
     pub damage: usize,
     pub range: f32,
+    pub linger: Option<balance::SplashLinger>,
 }
 
 #[derive(Component)]
-#[derive(Debug, Copy, Clone, PartialEq, Default)]
+#[derive(Debug, Clone, PartialEq)]
+struct LingeringPool {
+    /// Done every pulse.
+    pub damage: usize,
+    pub radius: f32,
+    pub weapon: PlayerWeapon,
+    pub time_left: f32,
+    pub pulse: Timer,
+}
+
+#[derive(Component)]
+#[derive(Debug, Copy, Clone, PartialEq)]
 struct SplashProjectileResidual {
     pub damage: usize,
     pub radius: f32,
+    pub weapon: PlayerWeapon,
 }
 
 #[derive(Debug, Copy, Clone, PartialEq)]
@@ -1146,9 +3288,14 @@ struct SplashResidue(pub Timer);
 
 #[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
 enum PlayerUpgrade {
-    HealthUpgrade,
-    AttackUpgrade,
-    SpeedUpgrade
+    /// Which of the cards on offer to pick, counting from 0.
+    Card(usize),
+    /// Swaps every card on offer for new ones.
+    Reroll,
+    /// Takes a card on offer out of the draft for the rest of the run.
+    Banish(usize),
+    /// Which of the upgrades the current weapon can take next, counting from 0.
+    WeaponUpgrade(usize),
 }
 
 #[derive(Resource)]
@@ -1166,15 +3313,24 @@ enum PowerUpType {
     Boost,
 }
 
-#[derive(Component)]
-#[derive(Debug, Copy, Clone, PartialEq, Default)]
-struct Position { pub x: f32, pub y: f32 }
+/// Everything in the game happens on a flat plane,
+/// so gameplay only cares about the x and y of a `Transform`.
+trait PlanarTransform {
+    fn position(&self) -> Vec2;
+    fn set_position(&mut self, position: Vec2);
+    fn translate_by(&mut self, offset: Vec2);
+    fn distance(&self, other: &Self) -> f32;
+}
 
-#[derive(Component)]
+#[derive(Component, Serialize, Deserialize)]
 #[derive(Debug, Copy, Clone, PartialEq)]
+#[serde(default, deny_unknown_fields)]
 struct PlayerStats {
     close_attack_damage: usize,
     close_attack_cooldown: f32,
+    close_attack_radius: f32,
+    /// How fast enemies the size of a chaser are pushed away. Bigger ones are pushed slower.
+    close_attack_knockback: f32,
 
     ranged_attack_damage: usize,
     ranged_attack_cooldown: f32,
@@ -1185,12 +3341,9 @@ struct PlayerStats {
     end_of_round_heal: usize,
 }
 
-#[derive(Debug, Copy, Clone, Default)]
-enum PlayerWeapon {
-    #[default] Normal,
-    Burst,
-    Splash,
-}
+/// Where the weapon is in `balance.weapons.roster`.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Default)]
+struct PlayerWeapon(pub usize);
 
 #[derive(Component)]
 #[derive(Debug, Clone)]
@@ -1198,20 +3351,37 @@ struct PlayerState {
     close_attack_timer: Timer,
     ranged_attack_timer: Timer,
     current_weapon: PlayerWeapon,
+    /// For each weapon, the upgrades taken from its tree so far, in order. How many there are is its level.
+    weapon_upgrades: Vec<Vec<usize>>,
+    /// Changes from upgrade cards, made to every weapon on top of its own upgrades.
+    weapon_mutations: Vec<balance::UpgradeEffect>,
     boost_time_left: f32,
+    /// How fast and which way the player moved last tick, for enemies that lead their shots.
+    velocity: Vec2,
 }
 
 #[derive(Component)]
 #[derive(Debug, Copy, Clone, PartialEq)]
 struct EnemyStats {
+    radius: f32,
+
     close_attack_damage: usize,
     close_attack_cooldown: f32,
 
+    /// Enemies only shoot if this is more than 0.
     ranged_attack_damage: usize,
     ranged_attack_cooldown: f32,
     ranged_attack_speed: f32,
+    shot_pattern: balance::ShotPattern,
 
     movement_speed: f32,
+    /// How close other enemies can get before this one steers away from them.
+    separation_radius: f32,
+    /// How much steering away from other enemies matters compared to heading for the player.
+    separation_strength: f32,
+
+    /// How long a dasher waits between dashes, or a healer between heals.
+    ability_cooldown: f32,
 }
 
 #[derive(Component)]
@@ -1219,6 +3389,10 @@ struct EnemyStats {
 struct EnemyState {
     close_attack_timer: Timer,
     ranged_attack_timer: Timer,
+    ability_timer: Timer,
+    dash_time_left: f32,
+    dash_velocity: Vec2,
+    knockback_velocity: Vec2,
 }
 
 #[derive(Component)]
@@ -1228,15 +3402,27 @@ struct Health {
     current_hp: usize
 }
 
-impl<From: Resource, To: Resource> Transition<From, To> {
-    fn new(_from: From, _to: To) -> Self {
-        Self { _make_the_compiler_happy: std::marker::PhantomData }
+impl Default for GameRng {
+    fn default() -> Self {
+        Self(StdRng::from_entropy())
+    }
+}
+
+impl GameRng {
+    fn seeded(seed: u64) -> Self {
+        Self(StdRng::seed_from_u64(seed))
+    }
+}
+
+impl PowerUpSpawnTimer {
+    fn new(spawn_interval: f32) -> Self {
+        Self(Timer::from_seconds(spawn_interval, TimerMode::Repeating))
     }
 }
 
 impl Default for PowerUpSpawnTimer {
     fn default() -> Self {
-        Self(Timer::from_seconds(4f32, TimerMode::Repeating))
+        Self::new(balance::PowerUpBalance::default().spawn_interval)
     }
 }
 
@@ -1248,8 +3434,8 @@ impl PowerUpType {
         }
     }
 
-    fn random() -> Self {
-        match random::<u8>() % 2 {
+    fn random(rng: &mut impl Rng) -> Self {
+        match rng.gen::<u8>() % 2 {
             0 => PowerUpType::Heal,
             1 => PowerUpType::Boost,
             _ => unreachable!("any number mod 2 is either 0 or 1"),
@@ -1257,6 +3443,36 @@ impl PowerUpType {
     }
 }
 
+impl RunStats {
+    /// Kills, waves and time add to the score, and damage taken comes off it.
+    fn score(&self, wave_reached: isize, score: &balance::ScoreBalance) -> u64 {
+        let earned = self.kill_score as f64
+            + self.bonus_score as f64
+            + wave_reached.max(0) as f64 * score.per_wave as f64
+            + self.play_time * score.per_second as f64;
+        let lost = self.damage_taken as f64 * score.per_damage_taken as f64;
+        (earned - lost).max(0.0) as u64
+    }
+}
+
+impl Draft {
+    fn new(draft: &balance::DraftBalance) -> Self {
+        Self {
+            rerolls_left: draft.rerolls_per_run,
+            banishes_left: draft.banishes_per_run,
+            ..default()
+        }
+    }
+
+    fn record_pick(&mut self, card: usize) {
+        self.picks.push(card);
+    }
+
+    fn picks_of(&self, card: usize) -> usize {
+        self.picks.iter().filter(|&&picked| picked == card).count()
+    }
+}
+
 impl PlayerUpgradeCounter {
     fn add_unused(&mut self) {
         self.unused_upgrades += 1;
@@ -1264,9 +3480,9 @@ impl PlayerUpgradeCounter {
 
     fn add_upgrade(&mut self, player_upgrade: PlayerUpgrade) {
         match player_upgrade {
-            PlayerUpgrade::HealthUpgrade => self.health_upgrades += 1,
-            PlayerUpgrade::SpeedUpgrade => self.speed_upgrades += 1,
-            PlayerUpgrade::AttackUpgrade => self.attack_upgrades += 1,
+            PlayerUpgrade::Card(_) => self.cards_picked += 1,
+            PlayerUpgrade::WeaponUpgrade(_) => self.weapon_upgrades += 1,
+            PlayerUpgrade::Reroll | PlayerUpgrade::Banish(_) => {},
         }
     }
 
@@ -1277,69 +3493,38 @@ impl PlayerUpgradeCounter {
     fn display_text(&self) -> String {
         format!(
             "Unused upgrades: {}\n\
-            Health upgrades [1]: {}\n\
-            Attack upgrades [2]: {}\n\
-            Speed upgrades [3]: {}\n",
+            Cards picked [1-3]: {}\n\
+            Weapon upgrades [4-9]: {}\n",
             self.unused_upgrades,
-            self.health_upgrades,
-            self.attack_upgrades,
-            self.speed_upgrades,
+            self.cards_picked,
+            self.weapon_upgrades,
         )
     }
 }
 
-impl Position {
-    pub fn new(x: f32, y: f32) -> Self {
-        Self { x, y }
+impl PlanarTransform for Transform {
+    fn position(&self) -> Vec2 {
+        self.translation.truncate()
     }
-    fn distance(&self, other: &Self) -> f32 {
-        let this: Vec2 = self.into();
-        let other: Vec2 = other.into();
-        this.distance(other)
+    /// Leaves the z coordinate alone, so draw order is kept.
+    fn set_position(&mut self, position: Vec2) {
+        self.translation.x = position.x;
+        self.translation.y = position.y;
     }
-}
-
-impl PlayerWeapon {
-    fn cycle_left(&mut self) {
-        *self = match self {
-            Self::Normal => Self::Splash,
-            Self::Splash => Self::Burst,
-            Self::Burst => Self::Normal,
-        }
+    fn translate_by(&mut self, offset: Vec2) {
+        self.set_position(self.position() + offset);
     }
-    pub fn cycle_right(&mut self) {
-        *self = match self {
-            Self::Normal => Self::Burst,
-            Self::Burst => Self::Splash,
-            Self::Splash => Self::Normal,
-        }
+    fn distance(&self, other: &Self) -> f32 {
+        self.position().distance(other.position())
     }
+}
 
-    pub fn cooldown_multiplier(&self) -> f32 {
-        match self {
-            Self::Normal => 1.2,
-            Self::Burst => 1.8,
-            Self::Splash => 2.5,
-        }
-    }
-    pub fn adjusted_damage(&self, base_damage: usize) -> usize {
-        match self {
-            Self::Normal => base_damage - 3,
-            Self::Burst => base_damage,
-            Self::Splash => base_damage + 2,
-        }
-    }
-    pub fn speed_multiplier(&self) -> f32 {
-        match self {
-            Self::Normal => 0.85,
-            Self::Burst => 1.2,
-            Self::Splash => 2.0,
-        }
-    }
-    pub fn adjusted_splash_damage(&self, base_damage: usize) -> usize {
-        match self {
-            Self::Splash => base_damage * 22 / 10,
-            _ => 0
+impl SplashProjectile {
+    fn new(splash: balance::WeaponSplash, ranged_damage: usize) -> Self {
+        Self {
+            damage: (ranged_damage as f32 * splash.damage_multiplier) as usize,
+            range: splash.range,
+            linger: splash.linger,
         }
     }
 }
@@ -1347,38 +3532,124 @@ impl PlayerWeapon {
 impl PlayerState {
     fn from_player_stats(player_stats: PlayerStats) -> Self {
         Self {
+            // Reset by hand when the close attack is used.
             close_attack_timer: Timer::from_seconds(
                 player_stats.close_attack_cooldown,
-                TimerMode::Repeating,
+                TimerMode::Once,
             ),
             ranged_attack_timer: Timer::from_seconds(
                 player_stats.ranged_attack_cooldown,
                 TimerMode::Repeating,
             ),
             current_weapon: PlayerWeapon::default(),
+            weapon_upgrades: Vec::new(),
+            weapon_mutations: Vec::new(),
             boost_time_left: 0.0,
+            velocity: Vec2::ZERO,
+        }
+    }
+
+    fn taken_upgrades(&self, weapon: PlayerWeapon) -> &[usize] {
+        self.weapon_upgrades.get(weapon.0).map_or(&[], Vec::as_slice)
+    }
+
+    fn take_upgrade(&mut self, weapon: PlayerWeapon, upgrade: usize) {
+        if self.weapon_upgrades.len() <= weapon.0 {
+            self.weapon_upgrades.resize(weapon.0 + 1, Vec::new());
+        }
+        self.weapon_upgrades[weapon.0].push(upgrade);
+    }
+
+    /// The weapon as it is now, with its upgrades and any mutations from upgrade cards.
+    fn weapon_definition(&self, weapon: PlayerWeapon, weapons: &balance::WeaponBalance) -> balance::WeaponDefinition {
+        weapons.definition(weapon).upgraded(self.taken_upgrades(weapon)).with_effects(&self.weapon_mutations)
+    }
+
+    fn reset_ranged_attack_timer(&mut self, stats: &PlayerStats, weapons: &balance::WeaponBalance) {
+        let cooldown_multiplier = self.weapon_definition(self.current_weapon, weapons).cooldown_multiplier;
+        self.ranged_attack_timer = Timer::from_seconds(
+            stats.ranged_attack_cooldown * cooldown_multiplier,
+            TimerMode::Repeating,
+        );
+    }
+}
+
+impl Boss {
+    fn new(bosses: &balance::BossBalance, ring_damage: usize) -> Self {
+        Self {
+            ring_timer: Timer::from_seconds(bosses.ring_cooldown, TimerMode::Repeating),
+            summon_timer: Timer::from_seconds(bosses.summon_cooldown, TimerMode::Repeating),
+            charge_timer: Timer::from_seconds(bosses.charge_cooldown, TimerMode::Repeating),
+            rings_fired: 0,
+            ring_damage,
         }
     }
 }
 
-impl EnemyStats {
-    fn new(
-        close_attack_damage: usize,
-        close_attack_cooldown: f32,
+impl EnemyKind {
+    const ALL: [Self; 7] = [
+        Self::Chaser, Self::Shooter, Self::Dasher, Self::Brute, Self::Splitter, Self::Healer, Self::Boss,
+    ];
+
+    fn color(&self) -> Srgba {
+        match self {
+            EnemyKind::Chaser => ORANGE_800,
+            EnemyKind::Shooter => RED_600,
+            EnemyKind::Dasher => YELLOW_600,
+            EnemyKind::Brute => STONE_700,
+            EnemyKind::Splitter => LIME_700,
+            EnemyKind::Healer => SKY_500,
+            EnemyKind::Boss => FUCHSIA_800,
+        }
+    }
+
+    /// What it's called in the balance file.
+    fn name(&self) -> &'static str {
+        match self {
+            EnemyKind::Chaser => "chaser",
+            EnemyKind::Shooter => "shooter",
+            EnemyKind::Dasher => "dasher",
+            EnemyKind::Brute => "brute",
+            EnemyKind::Splitter => "splitter",
+            EnemyKind::Healer => "healer",
+            EnemyKind::Boss => "boss",
+        }
+    }
+}
 
-        ranged_attack_damage: usize,
-        ranged_attack_cooldown: f32,
-        ranged_attack_speed: f32,
+impl EnemyStats {
+    fn scaled(mut self, scaling: balance::StatScaling) -> Self {
+        self.close_attack_damage = (self.close_attack_damage as f32 * scaling.damage) as usize;
+        self.ranged_attack_damage = (self.ranged_attack_damage as f32 * scaling.damage) as usize;
+        self.movement_speed *= scaling.speed;
+        self
+    }
 
-        movement_speed: f32,
-    ) -> Self {
+    fn for_kind(kind: EnemyKind, wave: isize, balance: &balance::Balance) -> Self {
+        let waves = &balance.waves;
+        let enemies = &balance.enemies;
+        let kind_stats = enemies.stats(kind);
+        let shot_pattern = enemies.shot_pattern(kind, wave);
         Self {
-            close_attack_damage,
-            close_attack_cooldown,
-            ranged_attack_damage,
-            ranged_attack_cooldown,
-            ranged_attack_speed,
-            movement_speed,
+            radius: kind_stats.radius,
+
+            close_attack_damage: (waves.melee_damage(wave) as f32 * kind_stats.melee_damage_multiplier) as usize,
+            close_attack_cooldown: waves.melee_cooldown(wave),
+
+            ranged_attack_damage: if shot_pattern.is_some() { waves.ranged_damage(wave) } else { 0 },
+            ranged_attack_cooldown: waves.ranged_attack_cooldown,
+            ranged_attack_speed: waves.ranged_attack_speed_base * waves.speed_multiplier(wave),
+            shot_pattern: shot_pattern.unwrap_or_default(),
+
+            movement_speed: waves.movement_speed_base * waves.speed_multiplier(wave) * kind_stats.speed_multiplier,
+            separation_radius: kind_stats.separation_radius,
+            separation_strength: kind_stats.separation_strength,
+
+            ability_cooldown: match kind {
+                EnemyKind::Dasher => enemies.dash_cooldown,
+                EnemyKind::Healer => enemies.heal_cooldown,
+                _ => 0.0,
+            },
         }
     }
 }
@@ -1390,22 +3661,34 @@ impl EnemyState {
                 Timer::from_seconds(enemy_stats.close_attack_cooldown, TimerMode::Repeating),
             ranged_attack_timer:
                 Timer::from_seconds(enemy_stats.ranged_attack_cooldown, TimerMode::Repeating),
+            // Reset by hand when the ability is used, and never repeating,
+            // so kinds without an ability can have a cooldown of 0.
+            ability_timer: Timer::from_seconds(enemy_stats.ability_cooldown, TimerMode::Once),
+            dash_time_left: 0.0,
+            dash_velocity: Vec2::ZERO,
+            knockback_velocity: Vec2::ZERO,
         }
     }
 
-    fn randomize_ranged_timer(&mut self) {
+    /// Moves in a straight line at `velocity` for `duration` seconds, whoever is in the way.
+    fn start_dash(&mut self, velocity: Vec2, duration: f32) {
+        self.dash_velocity = velocity;
+        self.dash_time_left = duration;
+    }
+
+    fn randomize_ranged_timer(&mut self, rng: &mut impl Rng) {
         use std::time::Duration;
 
         self.close_attack_timer.set_elapsed(Duration::from_secs_f32(
-            random::<f32>() * self.close_attack_timer.duration().as_secs_f32()
+            rng.gen::<f32>() * self.close_attack_timer.duration().as_secs_f32()
         ));
         self.ranged_attack_timer.set_elapsed(Duration::from_secs_f32(
-            random::<f32>() * self.ranged_attack_timer.duration().as_secs_f32()
+            rng.gen::<f32>() * self.ranged_attack_timer.duration().as_secs_f32()
         ));
     }
 
-    fn with_random_ranged_timer(mut self) -> Self {
-        self.randomize_ranged_timer();
+    fn with_random_ranged_timer(mut self, rng: &mut impl Rng) -> Self {
+        self.randomize_ranged_timer(rng);
         self
     }
 }
@@ -1524,6 +3807,8 @@ impl Default for PlayerStats {
         Self {
             close_attack_damage: 40usize,
             close_attack_cooldown: 1f32,
+            close_attack_radius: 180f32,
+            close_attack_knockback: 600f32,
 
             ranged_attack_damage: 6usize,
             ranged_attack_cooldown: 0.15f32,
@@ -1536,51 +3821,125 @@ impl Default for PlayerStats {
     }
 }
 
-impl std::ops::Add for Position {
-    type Output = Self;
-    fn add(self, other: Self) -> Self::Output {
-        Self {
-            x: self.x + other.x,
-            y: self.y + other.y,
-        }
-    }
-}
-impl std::ops::Sub for Position {
-    type Output = Self;
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    fn sub(self, other: Self) -> Self::Output {
-        Self {
-            x: self.x - other.x,
-            y: self.y - other.y,
-        }
-    }
-}
-impl std::ops::Mul<f32> for Position {
-    type Output = Self;
-    fn mul(self, multiplier: f32) -> Self::Output {
-        Self {
-            x: self.x * multiplier,
-            y: self.y * multiplier,
+    fn projectile_with_pierce(pierce: usize) -> Projectile {
+        Projectile {
+            damage: 10,
+            velocity: Vec2::X,
+            pierce_left: pierce,
+            already_hit: Vec::new(),
+            radius: BASE_PROJECTILE_RADIUS,
         }
     }
-}
 
-impl Into<Vec2> for Position {
-    fn into(self) -> Vec2 {
-        Vec2 {
-            x: self.x,
-            y: self.y
+    #[test]
+    fn no_pierce_stops_at_the_nearest_enemy() {
+        let mut projectile = projectile_with_pierce(0);
+        let [near, far] = [Entity::from_raw(1), Entity::from_raw(2)];
+
+        let (hits, spent) = pierce_through(&mut projectile, [(0.6, far), (0.2, near)]);
+        assert_eq!(hits, vec![(0.2, near)]);
+        assert!(spent);
+        assert_eq!(projectile.already_hit, vec![near]);
+    }
+
+    #[test]
+    fn pierce_goes_through_that_many_enemies_and_one_more() {
+        let mut projectile = projectile_with_pierce(2);
+        let enemies = (0..5).map(Entity::from_raw).collect::<Vec<_>>();
+        let reached = enemies.iter().enumerate().map(|(index, enemy)| (index as f32 * 0.1, *enemy));
+
+        let (hits, spent) = pierce_through(&mut projectile, reached);
+        assert_eq!(hits.iter().map(|(_, enemy)| *enemy).collect::<Vec<_>>(), enemies[..3]);
+        assert!(spent);
+        assert_eq!(projectile.pierce_left, 0);
+
+        // With pierce left over, it keeps going.
+        let mut projectile = projectile_with_pierce(5);
+        let (hits, spent) = pierce_through(&mut projectile, [(0.5, enemies[0])]);
+        assert_eq!(hits.len(), 1);
+        assert!(!spent);
+        assert_eq!(projectile.pierce_left, 4);
+    }
+
+    #[test]
+    fn overlapping_enemies_are_each_hit_once_across_ticks() {
+        let mut projectile = projectile_with_pierce(3);
+        let [first, second] = [Entity::from_raw(7), Entity::from_raw(3)];
+
+        // Both reached at the same point, so the tie is broken by entity.
+        let (hits, spent) = pierce_through(&mut projectile, [(0.4, first), (0.4, second)]);
+        assert_eq!(hits, vec![(0.4, second), (0.4, first)]);
+        assert!(!spent);
+
+        // Still overlapping them on the next tick doesn't hit them again.
+        let (hits, spent) = pierce_through(&mut projectile, [(0.0, first), (0.0, second)]);
+        assert!(hits.is_empty());
+        assert!(!spent);
+        assert_eq!(projectile.pierce_left, 1);
+    }
+
+    /// Fires the Burst once with the named upgrades taken, and returns the pellets it spawned.
+    fn fire_burst(upgrades: &[&str]) -> Vec<Projectile> {
+        let balance = balance::Balance::default();
+        let burst = balance.weapons.roster.iter()
+            .position(|definition| definition.name == "Burst")
+            .expect("the default roster has a Burst");
+        let weapon = PlayerWeapon(burst);
+
+        let stats = balance.player.stats;
+        let mut state = PlayerState::from_player_stats(stats);
+        state.current_weapon = weapon;
+        for name in upgrades {
+            let upgrade = balance.weapons.roster[burst].upgrades.iter()
+                .position(|upgrade| upgrade.name == *name)
+                .expect("the Burst has every upgrade asked for");
+            state.take_upgrade(weapon, upgrade);
         }
+        let cooldown = state.ranged_attack_timer.duration();
+        state.ranged_attack_timer.tick(cooldown);
+
+        let mut app = App::new();
+        app
+            .add_event::<DamageEvent>()
+            .insert_resource(balance)
+            .init_resource::<spatial::SpatialIndex>()
+            .insert_resource(PlayerInput { aim: Some(Vec2::new(100.0, 0.0)), firing: true, ..default() })
+            .add_systems(Update, player_ranged_attack);
+        app.world_mut().spawn((Player, stats, state, Transform::default()));
+        app.update();
+
+        app.world_mut()
+            .query_filtered::<&Projectile, With<PlayerProjectile>>()
+            .iter(app.world())
+            .cloned()
+            .collect()
+    }
+
+    #[test]
+    fn damage_upgrades_make_burst_pellets_hit_harder() {
+        let base = fire_burst(&[]);
+        let loaded = fire_burst(&["Loaded shells"]);
+        assert!(!base.is_empty());
+        assert_eq!(loaded.len(), base.len());
+        assert!(loaded.iter().all(|pellet| pellet.damage > base[0].damage));
+    }
+
+    #[test]
+    fn every_burst_pellet_gets_the_weapons_full_pierce() {
+        let balance = balance::Balance::default();
+        let base_pierce = balance.player.stats.ranged_attack_pierce;
+        let base = fire_burst(&[]);
+        assert!(!base.is_empty());
+        assert!(base.iter().all(|pellet| pellet.pierce_left == base_pierce));
+
+        // Slugs add 2 pierce on top of more damage.
+        let slugs = fire_burst(&["Loaded shells", "Tight choke", "Slugs"]);
+        assert!(!slugs.is_empty());
+        assert!(slugs.iter().all(|pellet| pellet.pierce_left == base_pierce + 2));
+        assert!(slugs.iter().all(|pellet| pellet.damage > base[0].damage));
     }
 }
-impl Into<Vec2> for &Position {
-    fn into(self) -> Vec2 {
-        (*self).into()
-    }
-}
-
-impl From<Vec2> for Position {
-    fn from(value: Vec2) -> Self {
-        Self::new(value.x, value.y)
-    }
-}
\ No newline at end of file
@@ -0,0 +1,500 @@
+//! Recording and playing back whole runs.
+//!
+//! Gameplay is deterministic given a seed and the `PlayerInput` of every tick,
+//! so that's all a replay stores. Ticks with the same input are stored once
+//! along with how many times in a row they happened, which keeps a replay of
+//! a long run down to a few kilobytes.
+//!
+//! `circles-infinity replay <file>` watches a replay, and
+//! `circles-infinity replay <file> --verify` plays it back without a window and
+//! exits with an error if it doesn't end the same way it did when it was recorded.
+//! The balance being reloaded partway through a run is noted in its replay, since the
+//! replay can't say what the new numbers were, and such a replay can't be verified.
+
+use std::path::{Path, PathBuf};
+
+use bevy::prelude::*;
+
+use crate::balance::Balance;
+use crate::simulation::{self, SimulationOutcome};
+use crate::{GameplaySystems, PlayerInput, PlayerUpgrade, RunSeed, RunState};
+
+const MAGIC: &[u8; 4] = b"CIRP";
+const VERSION: u8 = 6;
+
+/// Parses the arguments following `replay`.
+/// Returns the replay to watch, or exits the process if it was only being verified.
+pub fn replay_from_args(args: &[String], balance: &Balance) -> Result<Replay, String> {
+    let mut path = None;
+    let mut verify = false;
+    for arg in args {
+        match arg.as_str() {
+            "--verify" => verify = true,
+            _ if path.is_none() => path = Some(PathBuf::from(arg)),
+            _ => return Err(format!("unexpected argument `{arg}`")),
+        }
+    }
+
+    let path = path.ok_or("`replay` needs the path of a replay file")?;
+    let replay = Replay::read_from_file(&path)?;
+    replay.check_balance(balance).map_err(|err| format!("can't play back {}: {err}", path.display()))?;
+
+    if verify {
+        std::process::exit(verify_replay(&replay, balance));
+    }
+    if !replay.balance_reloads.is_empty() {
+        eprintln!("the balance was reloaded during this run, so the replay won't play out the same after that point");
+    }
+    Ok(replay)
+}
+
+/// Plays a replay back without a window, and compares how it ends
+/// to how it ended when it was recorded.
+/// The balance has to match the one the replay was recorded with, since it would drift otherwise.
+/// Returns the process exit code.
+pub fn verify_replay(replay: &Replay, balance: &Balance) -> i32 {
+    if let Err(err) = replay.check_balance(balance) {
+        eprintln!("{err}");
+        return 1;
+    }
+    if let Some(reload) = replay.balance_reloads.first() {
+        eprintln!(
+            "replay can't be verified: the balance was reloaded {} ticks into the run, \
+            and the replay doesn't have the numbers it was reloaded with",
+            reload.tick,
+        );
+        return 1;
+    }
+
+    let mut app = simulation::simulation_app(replay.seed, balance);
+    app
+        .insert_resource(ReplayPlayback::new(replay.clone()))
+        .add_systems(FixedUpdate, play_back_input
+            .before(GameplaySystems)
+            .run_if(in_state(RunState::Playing))
+        );
+
+    // The tick the player dies on only switches to the lose screen on the update after it,
+    // so a run that ended in death needs one more update to notice.
+    let max_ticks = replay.outcome.ticks + u64::from(replay.outcome.died);
+    let outcome = simulation::run_until_death(&mut app, replay.seed, max_ticks);
+    if outcome == replay.outcome {
+        println!("replay matches: {outcome}");
+        0
+    } else {
+        eprintln!("replay drifted!\nrecorded: {}\nreplayed: {outcome}", replay.outcome);
+        1
+    }
+}
+
+/// Writes a replay into the `replays` folder of the data directory.
+/// Returns where it was written, if it could be.
+pub fn write_replay_file(replay: &Replay) -> Option<PathBuf> {
+    let directory = crate::save::data_directory()?.join("replays");
+    let timestamp = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |since_epoch| since_epoch.as_secs());
+    let path = directory.join(format!(
+        "{timestamp}-seed-{}-wave-{}.replay",
+        replay.seed,
+        replay.outcome.wave_reached,
+    ));
+
+    let result = std::fs::create_dir_all(&directory)
+        .and_then(|_| std::fs::write(&path, replay.to_bytes()));
+    match result {
+        Ok(()) => Some(path),
+        Err(err) => {
+            warn!("Couldn't write replay {}: {err}", path.display());
+            None
+        }
+    }
+}
+
+/// Runs as the first gameplay system, so it sees exactly the input and balance the tick is about to use.
+pub fn record_player_input(
+    player_input: Res<PlayerInput>,
+    balance: Res<Balance>,
+    mut recorder: ResMut<ReplayRecorder>,
+) {
+    if balance.is_changed() {
+        recorder.record_balance(&balance);
+    }
+    recorder.record(*player_input);
+}
+
+pub fn start_recording(run_seed: Res<RunSeed>, balance: Res<Balance>, mut recorder: ResMut<ReplayRecorder>) {
+    *recorder = ReplayRecorder {
+        seed: run_seed.0,
+        balance_fingerprint: balance.fingerprint(),
+        inputs: Vec::new(),
+        balance_reloads: Vec::new(),
+    };
+}
+
+pub fn restart_playback(mut playback: ResMut<ReplayPlayback>) {
+    playback.next_input = 0;
+    playback.ticks_into_input = 0;
+}
+
+pub fn play_back_input(mut playback: ResMut<ReplayPlayback>, mut player_input: ResMut<PlayerInput>) {
+    *player_input = playback.next().unwrap_or_default();
+}
+
+/// A run of identical inputs.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct RecordedInput {
+    pub ticks: u32,
+    pub input: PlayerInput,
+}
+
+/// The balance being reloaded partway through a run.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct BalanceReload {
+    /// How many ticks into the run the new balance was first used.
+    pub tick: u64,
+    /// `Balance::fingerprint` of the new balance.
+    pub fingerprint: u64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Replay {
+    pub seed: u64,
+    /// `Balance::fingerprint` of the balance it was recorded with.
+    pub balance_fingerprint: u64,
+    pub inputs: Vec<RecordedInput>,
+    /// Every time the balance was reloaded during the run, in order.
+    pub balance_reloads: Vec<BalanceReload>,
+    /// How the run ended when it was recorded.
+    pub outcome: SimulationOutcome,
+}
+
+/// Collects the input of the current run.
+#[derive(Resource)]
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct ReplayRecorder {
+    pub seed: u64,
+    pub balance_fingerprint: u64,
+    pub inputs: Vec<RecordedInput>,
+    pub balance_reloads: Vec<BalanceReload>,
+}
+
+/// Feeds a replay's input to the game instead of the keyboard and mouse.
+#[derive(Resource)]
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReplayPlayback {
+    pub replay: Replay,
+    next_input: usize,
+    ticks_into_input: u32,
+}
+
+impl ReplayRecorder {
+    fn record(&mut self, mut input: PlayerInput) {
+        // Aiming only matters while firing, and leaving it out
+        // when it doesn't matter lets a lot more ticks share a run.
+        if !input.firing {
+            input.aim = None;
+        }
+
+        match self.inputs.last_mut() {
+            Some(last) if last.input == input && last.ticks < u32::MAX => last.ticks += 1,
+            _ => self.inputs.push(RecordedInput { ticks: 1, input }),
+        }
+    }
+
+    /// Notes the balance the next tick uses, if it's not the one the last tick used.
+    fn record_balance(&mut self, balance: &Balance) {
+        let fingerprint = balance.fingerprint();
+        let current = self.balance_reloads.last().map_or(self.balance_fingerprint, |reload| reload.fingerprint);
+        if fingerprint != current {
+            self.balance_reloads.push(BalanceReload { tick: self.ticks(), fingerprint });
+        }
+    }
+
+    pub fn ticks(&self) -> u64 {
+        self.inputs.iter().map(|recorded| u64::from(recorded.ticks)).sum()
+    }
+}
+
+impl ReplayPlayback {
+    pub fn new(replay: Replay) -> Self {
+        Self { replay, next_input: 0, ticks_into_input: 0 }
+    }
+}
+
+impl Iterator for ReplayPlayback {
+    type Item = PlayerInput;
+
+    fn next(&mut self) -> Option<PlayerInput> {
+        let recorded = self.replay.inputs.get(self.next_input)?;
+        let input = recorded.input;
+
+        self.ticks_into_input += 1;
+        if self.ticks_into_input >= recorded.ticks {
+            self.next_input += 1;
+            self.ticks_into_input = 0;
+        }
+        Some(input)
+    }
+}
+
+impl Replay {
+    pub fn check_balance(&self, balance: &Balance) -> Result<(), String> {
+        if balance.fingerprint() == self.balance_fingerprint {
+            Ok(())
+        } else {
+            Err("it was recorded with a different balance, so it wouldn't play out the same".to_string())
+        }
+    }
+
+    pub fn read_from_file(path: &Path) -> Result<Self, String> {
+        let bytes = std::fs::read(path)
+            .map_err(|err| format!("couldn't read replay {}: {err}", path.display()))?;
+        Self::from_bytes(&bytes)
+            .map_err(|err| format!("{} isn't a valid replay: {err}", path.display()))
+    }
+
+    /// Everything is little endian.
+    /// The header is the magic bytes, the version, the seed, the balance fingerprint and the outcome,
+    /// followed by the number of recorded inputs and then each recorded input,
+    /// and last the number of balance reloads and then the tick and fingerprint of each.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(MAGIC);
+        bytes.push(VERSION);
+        bytes.extend_from_slice(&self.seed.to_le_bytes());
+        bytes.extend_from_slice(&self.balance_fingerprint.to_le_bytes());
+        bytes.extend_from_slice(&(self.outcome.wave_reached as i64).to_le_bytes());
+        bytes.extend_from_slice(&self.outcome.enemies_killed.to_le_bytes());
+        bytes.extend_from_slice(&self.outcome.ticks.to_le_bytes());
+        bytes.push(u8::from(self.outcome.died));
+
+        bytes.extend_from_slice(&(self.inputs.len() as u32).to_le_bytes());
+        for recorded in &self.inputs {
+            let input = recorded.input;
+            // Upgrades that pick a card or choice are followed by which one it was.
+            let (upgrade, index) = match input.upgrade {
+                None => (0, None),
+                Some(PlayerUpgrade::Card(slot)) => (1, Some(slot)),
+                Some(PlayerUpgrade::Banish(slot)) => (2, Some(slot)),
+                Some(PlayerUpgrade::WeaponUpgrade(choice)) => (3, Some(choice)),
+                Some(PlayerUpgrade::Reroll) => (4, None),
+            };
+            let flags = u8::from(input.firing)
+                | u8::from(input.aim.is_some()) << 1
+                | u8::from(input.cycle_weapon_left) << 2
+                | u8::from(input.cycle_weapon_right) << 3
+                | u8::from(input.call_wave) << 4
+                | u8::from(input.close_attack) << 5;
+
+            bytes.extend_from_slice(&recorded.ticks.to_le_bytes());
+            bytes.push(flags);
+            bytes.push(upgrade);
+            if let Some(index) = index {
+                bytes.extend_from_slice(&(index as u64).to_le_bytes());
+            }
+            bytes.extend_from_slice(&input.movement.x.to_le_bytes());
+            bytes.extend_from_slice(&input.movement.y.to_le_bytes());
+            if let Some(aim) = input.aim {
+                bytes.extend_from_slice(&aim.x.to_le_bytes());
+                bytes.extend_from_slice(&aim.y.to_le_bytes());
+            }
+        }
+
+        bytes.extend_from_slice(&(self.balance_reloads.len() as u32).to_le_bytes());
+        for reload in &self.balance_reloads {
+            bytes.extend_from_slice(&reload.tick.to_le_bytes());
+            bytes.extend_from_slice(&reload.fingerprint.to_le_bytes());
+        }
+
+        bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, String> {
+        let mut reader = ByteReader(bytes);
+
+        if &reader.take::<4>()? != MAGIC {
+            return Err("it doesn't start with the replay magic bytes".to_string());
+        }
+        let [version] = reader.take::<1>()?;
+        if version != VERSION {
+            return Err(format!("it's version {version}, but only version {VERSION} is supported"));
+        }
+
+        let seed = u64::from_le_bytes(reader.take()?);
+        let balance_fingerprint = u64::from_le_bytes(reader.take()?);
+        let outcome = SimulationOutcome {
+            seed,
+            wave_reached: i64::from_le_bytes(reader.take()?) as isize,
+            enemies_killed: u64::from_le_bytes(reader.take()?),
+            ticks: u64::from_le_bytes(reader.take()?),
+            died: reader.take::<1>()? != [0],
+        };
+
+        let input_count = u32::from_le_bytes(reader.take()?);
+        let mut inputs = Vec::with_capacity(input_count.min(1 << 16) as usize);
+        for _ in 0..input_count {
+            let ticks = u32::from_le_bytes(reader.take()?);
+            let [flags, upgrade] = reader.take::<2>()?;
+            let mut index = || {
+                let index = u64::from_le_bytes(reader.take()?);
+                usize::try_from(index).map_err(|_| format!("it picks upgrade {index}, which is out of range"))
+            };
+            let upgrade = match upgrade {
+                0 => None,
+                1 => Some(PlayerUpgrade::Card(index()?)),
+                2 => Some(PlayerUpgrade::Banish(index()?)),
+                3 => Some(PlayerUpgrade::WeaponUpgrade(index()?)),
+                4 => Some(PlayerUpgrade::Reroll),
+                kind => return Err(format!("it has an unknown kind of upgrade {kind}")),
+            };
+            let movement = Vec2::new(
+                f32::from_le_bytes(reader.take()?),
+                f32::from_le_bytes(reader.take()?),
+            );
+            let aim = if flags & 1 << 1 != 0 {
+                Some(Vec2::new(
+                    f32::from_le_bytes(reader.take()?),
+                    f32::from_le_bytes(reader.take()?),
+                ))
+            } else {
+                None
+            };
+
+            inputs.push(RecordedInput {
+                ticks,
+                input: PlayerInput {
+                    movement,
+                    aim,
+                    firing: flags & 1 != 0,
+                    cycle_weapon_left: flags & 1 << 2 != 0,
+                    cycle_weapon_right: flags & 1 << 3 != 0,
+                    upgrade,
+                    call_wave: flags & 1 << 4 != 0,
+                    close_attack: flags & 1 << 5 != 0,
+                },
+            });
+        }
+
+        let reload_count = u32::from_le_bytes(reader.take()?);
+        let mut balance_reloads = Vec::with_capacity(reload_count.min(1 << 16) as usize);
+        for _ in 0..reload_count {
+            balance_reloads.push(BalanceReload {
+                tick: u64::from_le_bytes(reader.take()?),
+                fingerprint: u64::from_le_bytes(reader.take()?),
+            });
+        }
+
+        if !reader.0.is_empty() {
+            return Err(format!("it has {} unexpected bytes at the end", reader.0.len()));
+        }
+
+        Ok(Self { seed, balance_fingerprint, inputs, balance_reloads, outcome })
+    }
+}
+
+struct ByteReader<'a>(&'a [u8]);
+
+impl ByteReader<'_> {
+    fn take<const N: usize>(&mut self) -> Result<[u8; N], String> {
+        if self.0.len() < N {
+            return Err("it ends too early".to_string());
+        }
+        let (taken, rest) = self.0.split_at(N);
+        self.0 = rest;
+        Ok(taken.try_into().expect("split_at returns exactly N bytes"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::simulation::InputScript;
+
+    #[test]
+    fn recorded_run_verifies_after_a_round_trip() {
+        let balance = Balance::default();
+        let seed = 3;
+        let mut app = simulation::simulation_app(seed, &balance);
+        app
+            .insert_resource(InputScript::Kite)
+            .add_systems(FixedUpdate, simulation::drive_scripted_input
+                .before(GameplaySystems)
+                .run_if(in_state(RunState::Playing))
+            );
+        let outcome = simulation::run_until_death(&mut app, seed, 64 * 30);
+
+        let recorder = app.world().resource::<ReplayRecorder>();
+        let replay = Replay {
+            seed,
+            balance_fingerprint: recorder.balance_fingerprint,
+            inputs: recorder.inputs.clone(),
+            balance_reloads: recorder.balance_reloads.clone(),
+            outcome: SimulationOutcome { ticks: recorder.ticks(), ..outcome },
+        };
+        let read_back = Replay::from_bytes(&replay.to_bytes()).expect("a replay should read back what it wrote");
+        assert_eq!(read_back, replay);
+        assert_eq!(verify_replay(&read_back, &balance), 0);
+    }
+
+    #[test]
+    fn replay_with_another_balance_is_rejected() {
+        let balance = Balance::default();
+        let replay = Replay {
+            seed: 0,
+            balance_fingerprint: balance.fingerprint(),
+            inputs: Vec::new(),
+            balance_reloads: Vec::new(),
+            outcome: SimulationOutcome { seed: 0, wave_reached: 0, enemies_killed: 0, ticks: 0, died: false },
+        };
+        assert!(replay.check_balance(&balance).is_ok());
+
+        let mut tweaked = balance.clone();
+        tweaked.player.health += 1;
+        assert!(replay.check_balance(&tweaked).is_err());
+        assert_eq!(verify_replay(&replay, &tweaked), 1);
+    }
+
+    #[test]
+    fn balance_reloaded_during_a_run_is_recorded_and_unverifiable() {
+        let balance = Balance::default();
+        let seed = 5;
+        let mut app = simulation::simulation_app(seed, &balance);
+        simulation::run_until_death(&mut app, seed, 60);
+
+        let mut reloaded = balance.clone();
+        reloaded.power_ups.spawn_interval += 1.0;
+        app.insert_resource(reloaded.clone());
+        let outcome = simulation::run_until_death(&mut app, seed, 60);
+
+        let recorder = app.world().resource::<ReplayRecorder>();
+        assert_eq!(recorder.balance_reloads.len(), 1);
+        let reload = recorder.balance_reloads[0];
+        assert_eq!(reload.fingerprint, reloaded.fingerprint());
+        assert!(reload.tick >= 60 && reload.tick < recorder.ticks());
+
+        let replay = Replay {
+            seed,
+            balance_fingerprint: recorder.balance_fingerprint,
+            inputs: recorder.inputs.clone(),
+            balance_reloads: recorder.balance_reloads.clone(),
+            outcome: SimulationOutcome { ticks: recorder.ticks(), ..outcome },
+        };
+        let read_back = Replay::from_bytes(&replay.to_bytes()).expect("a replay should read back what it wrote");
+        assert_eq!(read_back, replay);
+        assert_eq!(verify_replay(&read_back, &balance), 1);
+    }
+
+    #[test]
+    fn upgrade_picks_keep_their_index() {
+        let input = PlayerInput { upgrade: Some(PlayerUpgrade::Card(40)), ..default() };
+        let replay = Replay {
+            seed: 0,
+            balance_fingerprint: Balance::default().fingerprint(),
+            inputs: vec![RecordedInput { ticks: 1, input }],
+            balance_reloads: Vec::new(),
+            outcome: SimulationOutcome { seed: 0, wave_reached: 0, enemies_killed: 0, ticks: 1, died: false },
+        };
+        assert_eq!(Replay::from_bytes(&replay.to_bytes()), Ok(replay));
+    }
+}
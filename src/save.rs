@@ -0,0 +1,106 @@
+use std::path::PathBuf;
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+const SAVE_DIRECTORY_NAME: &str = "circles-infinity";
+const SAVE_FILE_NAME: &str = "save.ron";
+
+/// Loads the save file, falling back to a fresh save if there isn't one.
+///
+/// A save file that can't be read or parsed is moved out of the way
+/// (to `save.ron.corrupt`) so the next write doesn't silently destroy it,
+/// and the game carries on with a fresh save.
+pub fn load_save_data() -> SaveData {
+    let Some(path) = save_file_path() else {
+        warn!("Couldn't find a data directory, progress won't be saved");
+        return SaveData::default();
+    };
+
+    let contents = match std::fs::read_to_string(&path) {
+        Ok(contents) => contents,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return SaveData::default(),
+        Err(err) => {
+            warn!("Couldn't read save file {}: {err}", path.display());
+            return SaveData::default();
+        }
+    };
+
+    match ron::from_str(&contents) {
+        Ok(save_data) => save_data,
+        Err(err) => {
+            warn!("Save file {} is corrupt, starting fresh: {err}", path.display());
+            let backup = path.with_extension("ron.corrupt");
+            if let Err(err) = std::fs::rename(&path, &backup) {
+                warn!("Couldn't move corrupt save file to {}: {err}", backup.display());
+            }
+            SaveData::default()
+        }
+    }
+}
+
+/// Writes the save file.
+/// The data is written to a temporary file first and then renamed over the
+/// old save, so a crash halfway through never leaves a half-written save behind.
+pub fn write_save_data(save_data: &SaveData) {
+    let Some(path) = save_file_path() else { return };
+
+    let result = (|| -> Result<(), Box<dyn std::error::Error>> {
+        if let Some(directory) = path.parent() {
+            std::fs::create_dir_all(directory)?;
+        }
+        let contents = ron::ser::to_string_pretty(save_data, ron::ser::PrettyConfig::default())?;
+        let temporary_path = path.with_extension("ron.tmp");
+        std::fs::write(&temporary_path, contents)?;
+        std::fs::rename(&temporary_path, &path)?;
+        Ok(())
+    })();
+
+    if let Err(err) = result {
+        warn!("Couldn't write save file {}: {err}", path.display());
+    }
+}
+
+/// Where everything the game writes to disk lives.
+/// On Linux this is `$XDG_DATA_HOME/circles-infinity`,
+/// on Windows `%APPDATA%` and on macOS `~/Library/Application Support`.
+pub fn data_directory() -> Option<PathBuf> {
+    dirs::data_dir().map(|directory| directory.join(SAVE_DIRECTORY_NAME))
+}
+
+fn save_file_path() -> Option<PathBuf> {
+    data_directory().map(|directory| directory.join(SAVE_FILE_NAME))
+}
+
+/// Everything that is kept between launches of the game.
+/// Missing fields are filled in with their defaults,
+/// so older save files keep loading when new fields are added.
+#[derive(Resource, Serialize, Deserialize)]
+#[derive(Debug, Copy, Clone, PartialEq, Default)]
+#[serde(default)]
+pub struct SaveData {
+    pub best_wave: isize,
+    pub best_score: u64,
+    pub total_runs: u64,
+    pub total_enemies_killed: u64,
+    /// In seconds, not counting time spent paused.
+    pub total_play_time: f64,
+}
+
+impl SaveData {
+    /// Folds a finished run into the lifetime statistics.
+    /// Returns whether the run set a new best wave.
+    pub fn record_run(&mut self, wave_reached: isize, enemies_killed: u64, play_time: f64, score: u64) -> bool {
+        self.total_runs += 1;
+        self.total_enemies_killed += enemies_killed;
+        self.total_play_time += play_time;
+        self.best_score = self.best_score.max(score);
+
+        if wave_reached > self.best_wave {
+            self.best_wave = wave_reached;
+            true
+        } else {
+            false
+        }
+    }
+}
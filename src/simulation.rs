@@ -0,0 +1,335 @@
+//! Headless runs of the gameplay systems, for balance testing.
+//!
+//! `circles-infinity simulate --seed 42 --runs 100 --script kite --min-wave 5`
+//! plays 100 whole runs without a window as fast as the machine allows,
+//! prints the wave each one died on, and exits with an error if any run
+//! didn't reach wave 5. Every run is seeded, so the same arguments always
+//! print the same results. Runs use the same balance file as the game,
+//! so `--balance <file>` can be used to try out a change before making it.
+
+use bevy::prelude::*;
+use bevy::state::app::StatesPlugin;
+use bevy::time::TimeUpdateStrategy;
+
+use crate::balance::Balance;
+use crate::{
+    ChosenSeed, Draft, Enemy, GameScreen, GameplayPlugin, GameplaySystems, Intermission, PlanarTransform,
+    Player, PlayerInput, PlayerState, PlayerUpgrade, PlayerUpgradeCounter, RunState, RunStats, WaveCounter,
+};
+
+/// Parses the arguments following `simulate` and runs the simulations.
+/// Returns the process exit code.
+pub fn run_from_args(args: &[String], balance: &Balance) -> i32 {
+    let options = match SimulationOptions::parse(args) {
+        Ok(options) => options,
+        Err(err) => {
+            eprintln!("{err}\n\n{}", SimulationOptions::USAGE);
+            return 2;
+        }
+    };
+
+    let mut failed_runs = 0;
+    for run in 0..options.runs {
+        let seed = options.seed.wrapping_add(run);
+        let outcome = simulate_run(seed, options.script, options.max_ticks, balance);
+        println!("{outcome}");
+
+        if outcome.wave_reached < options.min_wave {
+            failed_runs += 1;
+        }
+    }
+
+    if failed_runs > 0 {
+        eprintln!("{failed_runs} of {} runs didn't reach wave {}", options.runs, options.min_wave);
+        1
+    } else {
+        0
+    }
+}
+
+/// Plays a single run from start to finish, or until `max_ticks` fixed ticks have passed.
+pub fn simulate_run(seed: u64, script: InputScript, max_ticks: u64, balance: &Balance) -> SimulationOutcome {
+    let mut app = simulation_app(seed, balance);
+    app
+        .insert_resource(script)
+        .add_systems(FixedUpdate, drive_scripted_input
+            .before(GameplaySystems)
+            .run_if(in_state(RunState::Playing))
+        );
+
+    run_until_death(&mut app, seed, max_ticks)
+}
+
+/// Updates an app made by `simulation_app` until the player dies
+/// or `max_ticks` fixed ticks have passed.
+pub fn run_until_death(app: &mut App, seed: u64, max_ticks: u64) -> SimulationOutcome {
+    let mut ticks = 0;
+    let mut died = false;
+    // The first update only starts the clock, so it doesn't count as a tick.
+    app.update();
+    while ticks < max_ticks {
+        app.update();
+        if *app.world().resource::<State<GameScreen>>().get() != GameScreen::Running {
+            died = true;
+            break;
+        }
+        ticks += 1;
+    }
+
+    let world = app.world();
+    let run_stats = world.resource::<RunStats>();
+    SimulationOutcome {
+        seed,
+        wave_reached: world.resource::<WaveCounter>().0,
+        enemies_killed: run_stats.enemies_killed,
+        ticks,
+        died,
+    }
+}
+
+/// Builds an app that runs exactly one gameplay tick per `App::update`,
+/// with the run already started.
+/// Nothing drives `PlayerInput` yet, that's up to the caller.
+pub fn simulation_app(seed: u64, balance: &Balance) -> App {
+    let mut app = App::new();
+    app
+        .add_plugins((MinimalPlugins, StatesPlugin, GameplayPlugin))
+        .insert_resource(TimeUpdateStrategy::ManualDuration(Time::<Fixed>::default().timestep()))
+        .insert_resource(ChosenSeed(Some(seed)))
+        .insert_resource(balance.clone());
+    app.world_mut().resource_mut::<NextState<GameScreen>>().set(GameScreen::Running);
+    app.finish();
+    app.cleanup();
+    app
+}
+
+pub fn drive_scripted_input(
+    script: Res<InputScript>,
+    mut player_input: ResMut<PlayerInput>,
+    player_upgrade_counter: Res<PlayerUpgradeCounter>,
+    draft: Res<Draft>,
+    intermission: Res<Intermission>,
+    balance: Res<Balance>,
+    player: Query<(&Transform, &PlayerState), With<Player>>,
+    enemies: Query<&Transform, With<Enemy>>,
+) {
+    const KITE_DISTANCE: f32 = 400.0;
+    const CLOSE_ATTACK_DISTANCE: f32 = 150.0;
+
+    let (player_transform, state) = player.single();
+    let player_pos = player_transform.position();
+    let closest_enemy = enemies.iter()
+        .map(|transform| transform.position())
+        .min_by(|a, b| a.distance_squared(player_pos).total_cmp(&b.distance_squared(player_pos)));
+
+    if *script == InputScript::Idle {
+        *player_input = PlayerInput::default();
+        return;
+    }
+
+    player_input.aim = closest_enemy;
+    player_input.firing = closest_enemy.is_some();
+    player_input.close_attack = closest_enemy
+        .is_some_and(|enemy_pos| enemy_pos.distance(player_pos) < CLOSE_ATTACK_DISTANCE);
+    player_input.movement = match (*script, closest_enemy) {
+        (InputScript::Kite, Some(enemy_pos)) if enemy_pos.distance(player_pos) < KITE_DISTANCE => {
+            (player_pos - enemy_pos).normalize_or_zero()
+        },
+        _ => Vec2::ZERO,
+    };
+
+    // Spread upgrades evenly between the first card on offer and the weapon, starting with the card.
+    // The weapon is only in the running between waves, when it can be upgraded, and always takes the first branch.
+    if player_upgrade_counter.unused_upgrades > 0 {
+        let counter = *player_upgrade_counter;
+        let weapon = state.current_weapon;
+        let can_upgrade_weapon = intermission.0.is_some()
+            && !balance.weapons.definition(weapon).next_upgrades(state.taken_upgrades(weapon)).is_empty();
+        player_input.upgrade = (!draft.offer.is_empty())
+            .then_some((PlayerUpgrade::Card(0), counter.cards_picked))
+            .into_iter()
+            .chain(can_upgrade_weapon.then_some((PlayerUpgrade::WeaponUpgrade(0), counter.weapon_upgrades)))
+            .min_by_key(|(_, taken)| *taken)
+            .map(|(upgrade, _)| upgrade);
+    }
+}
+
+/// How a simulated player behaves.
+#[derive(Resource)]
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Default)]
+pub enum InputScript {
+    /// Stands still and shoots at the closest enemy.
+    Turret,
+    /// Shoots at the closest enemy and backs away from it when it gets too close.
+    #[default] Kite,
+    /// Stands still and never shoots or upgrades.
+    Idle,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct SimulationOutcome {
+    pub seed: u64,
+    pub wave_reached: isize,
+    pub enemies_killed: u64,
+    pub ticks: u64,
+    /// `false` if the run was cut off by the tick limit instead.
+    pub died: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct SimulationOptions {
+    seed: u64,
+    runs: u64,
+    script: InputScript,
+    max_ticks: u64,
+    min_wave: isize,
+}
+
+impl std::str::FromStr for InputScript {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "turret" => Ok(Self::Turret),
+            "kite" => Ok(Self::Kite),
+            "idle" => Ok(Self::Idle),
+            _ => Err(format!("unknown script `{s}`, expected `turret`, `kite` or `idle`")),
+        }
+    }
+}
+
+impl std::fmt::Display for SimulationOutcome {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "seed {}: {} on wave {} after {} ticks, {} enemies killed",
+            self.seed,
+            if self.died { "died" } else { "still alive" },
+            self.wave_reached,
+            self.ticks,
+            self.enemies_killed,
+        )
+    }
+}
+
+impl SimulationOptions {
+    const USAGE: &'static str = "usage: circles-infinity simulate [--seed <u64>] [--runs <count>] \
+        [--script turret|kite|idle] [--max-ticks <count>] [--min-wave <wave>]";
+
+    fn parse(args: &[String]) -> Result<Self, String> {
+        let mut options = Self::default();
+        let mut args = args.iter();
+
+        while let Some(flag) = args.next() {
+            let mut value = || args.next().ok_or_else(|| format!("`{flag}` needs a value"));
+            match flag.as_str() {
+                "--seed" => options.seed = parse_value(flag, value()?)?,
+                "--runs" => options.runs = parse_value(flag, value()?)?,
+                "--script" => options.script = parse_value(flag, value()?)?,
+                "--max-ticks" => options.max_ticks = parse_value(flag, value()?)?,
+                "--min-wave" => options.min_wave = parse_value(flag, value()?)?,
+                _ => return Err(format!("unknown argument `{flag}`")),
+            }
+        }
+
+        Ok(options)
+    }
+}
+
+impl Default for SimulationOptions {
+    fn default() -> Self {
+        Self {
+            seed: 0,
+            runs: 1,
+            script: InputScript::default(),
+            // An hour of play at the default 64 ticks per second.
+            max_ticks: 64 * 60 * 60,
+            min_wave: 0,
+        }
+    }
+}
+
+fn parse_value<T: std::str::FromStr>(flag: &str, value: &str) -> Result<T, String>
+where
+    T::Err: std::fmt::Display,
+{
+    value.parse().map_err(|err| format!("invalid value `{value}` for `{flag}`: {err}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::EnemyKind;
+
+    #[test]
+    fn kiting_player_gets_past_the_first_waves() {
+        // Five minutes of play at 64 ticks per second.
+        const MAX_TICKS: u64 = 64 * 60 * 5;
+        const MIN_WAVE: isize = 3;
+
+        let balance = Balance::default();
+        for seed in [0, 1, 42] {
+            let outcome = simulate_run(seed, InputScript::Kite, MAX_TICKS, &balance);
+            assert!(outcome.wave_reached >= MIN_WAVE, "expected to reach wave {MIN_WAVE}, but {outcome}");
+        }
+    }
+
+    #[test]
+    fn simulated_runs_are_deterministic() {
+        let balance = Balance::default();
+        let first = simulate_run(7, InputScript::Kite, 64 * 60, &balance);
+        let second = simulate_run(7, InputScript::Kite, 64 * 60, &balance);
+        assert_eq!(first, second);
+    }
+
+    #[test]
+    fn enemies_spread_out_around_the_player() {
+        // Eight seconds at 64 ticks per second.
+        const TICKS: u64 = 64 * 8;
+
+        let balance = Balance::default();
+        let spacing = crowd_spacing(0, TICKS, balance.clone());
+        let mut unseparated_balance = balance;
+        for kind in EnemyKind::ALL {
+            unseparated_balance.enemies.stats_mut(kind).separation_strength = 0.0;
+        }
+        let unseparated_spacing = crowd_spacing(0, TICKS, unseparated_balance);
+
+        assert!(spacing >= crate::ENEMY_RADIUS, "enemies crowded to {spacing:.1} apart on average");
+        assert!(
+            spacing > unseparated_spacing,
+            "separation didn't help: {spacing:.1} apart with it, {unseparated_spacing:.1} without",
+        );
+    }
+
+    /// Lets the first wave close in on a player who stands still and can't die,
+    /// then measures the average distance between every pair of enemies.
+    fn crowd_spacing(seed: u64, ticks: u64, mut balance: Balance) -> f32 {
+        balance.player.health = usize::MAX / 2;
+
+        let mut app = simulation_app(seed, &balance);
+        app
+            .insert_resource(InputScript::Idle)
+            .add_systems(FixedUpdate, drive_scripted_input
+                .before(GameplaySystems)
+                .run_if(in_state(RunState::Playing))
+            );
+        run_until_death(&mut app, seed, ticks);
+
+        let world = app.world_mut();
+        let enemies = world.query_filtered::<&Transform, With<Enemy>>()
+            .iter(world)
+            .map(|transform| transform.position())
+            .collect::<Vec<_>>();
+
+        let mut total_distance = 0.0;
+        let mut pairs = 0;
+        for (index, a) in enemies.iter().enumerate() {
+            for b in &enemies[index + 1..] {
+                total_distance += a.distance(*b);
+                pairs += 1;
+            }
+        }
+        if pairs == 0 { 0.0 } else { total_distance / pairs as f32 }
+    }
+}
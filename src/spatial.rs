@@ -0,0 +1,47 @@
+//! The spatial grids of everything that can be collided with, rebuilt every tick.
+
+use bevy::prelude::*;
+
+pub use game_jam_entry::grid::{sweep_circle, GridEntry, SpatialGrid};
+
+use crate::{Enemy, EnemyProjectile, EnemyStats, PlanarTransform, PowerUp, Projectile, POWER_UP_RADIUS, POWER_UP_PULSE};
+
+/// Runs after every kind of thing in it has finished spawning and moving for the tick,
+/// and before anything checks for collisions.
+pub fn rebuild_spatial_index(
+    time: Res<Time>,
+    mut index: ResMut<SpatialIndex>,
+    enemies: Query<(Entity, &Transform, &EnemyStats), With<Enemy>>,
+    enemy_projectiles: Query<(Entity, &Transform, &Projectile), With<EnemyProjectile>>,
+    power_ups: Query<(Entity, &Transform), With<PowerUp>>,
+) {
+    index.enemies.clear();
+    for (id, transform, stats) in enemies.iter() {
+        index.enemies.insert(id, transform.position(), stats.radius);
+    }
+
+    // Enemy projectiles cover the whole path they're about to take this tick,
+    // so a fast one can't skip past the player.
+    index.enemy_projectiles.clear();
+    for (id, transform, projectile) in enemy_projectiles.iter() {
+        let start = transform.position();
+        let end = start + projectile.velocity * time.delta_seconds();
+        index.enemy_projectiles.insert(id, start.lerp(end, 0.5), start.distance(end) / 2.0 + projectile.radius);
+    }
+
+    index.power_ups.clear();
+    for (id, transform) in power_ups.iter() {
+        index.power_ups.insert(id, transform.position(), POWER_UP_RADIUS + POWER_UP_PULSE);
+    }
+}
+
+/// One grid for each kind of thing that gets collided with.
+/// The player and their projectiles aren't in here,
+/// since they're the ones asking what they've hit.
+#[derive(Resource)]
+#[derive(Debug, Clone, Default)]
+pub struct SpatialIndex {
+    pub enemies: SpatialGrid,
+    pub enemy_projectiles: SpatialGrid,
+    pub power_ups: SpatialGrid,
+}